@@ -1,13 +1,23 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
 use std::rc::Rc;
 
 use phf::phf_map;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 
 use super::{Builtin, Operator};
 use crate::scope::{Scope, StackVal};
 use crate::utils;
-use crate::utils::error::{night_err, Status};
+use crate::utils::error::{night_err, NightError, Status};
+use crate::utils::csv;
+use crate::utils::format;
+use crate::utils::json;
+use crate::utils::time;
 use crate::utils::function::{self, ComposedFunc, CurriedFunc};
-use crate::value::Value;
+use crate::value::{self, Map, Struct, Value};
 
 // TODO: fix this + create required `arity` functions
 // not sure if this will be used for `define_builtins` as well. It probably will in
@@ -30,6 +40,10 @@ macro_rules! _define_internal {
     ($scope:expr, 1(2), $def:expr) => {
         function::arity2_1($def, $scope)
     };
+    // 3 args 1 output
+    ($scope:expr, 1(3), $def:expr) => {
+        function::arity3_1($def, $scope)
+    };
     // all other cases
     // TODO: maybe other common patterns supported
     ($scope:expr, $a:tt($b:tt), $def:expr) => {
@@ -37,6 +51,16 @@ macro_rules! _define_internal {
     };
 }
 
+// Stack effect of an `a(b)` annotation as (inputs, outputs), `_(_)` marks a variable effect
+macro_rules! _arity {
+    (_(_)) => {
+        None
+    };
+    ($a:literal($b:literal)) => {
+        Some(($b, $a))
+    };
+}
+
 macro_rules! define_ops {
     ($($rep:expr => ($tok:pat, $lit:expr, $a:tt($b:tt): $def:expr));*;) => {
         pub static OP_MAP: phf::Map<&'static str, Operator> = phf_map! {
@@ -78,6 +102,14 @@ macro_rules! define_ops {
                     ),*
                 }
             }
+
+            pub fn arity(&self) -> Option<(usize, usize)> {
+                match self {
+                    $(
+                        $tok => _arity!($a($b))
+                    ),*
+                }
+            }
         }
     }
 }
@@ -106,6 +138,14 @@ macro_rules! define_builtins {
                     ),*
                 }
             }
+
+            pub fn arity(&self) -> Option<(usize, usize)> {
+                match self {
+                    $(
+                        $tok => _arity!($a($b))
+                    ),*
+                }
+            }
         }
     }
 }
@@ -136,19 +176,29 @@ define_ops! {
 
     "~" => (Operator::Not, "not", 1(1): op_not);
 
-    ";" => (Operator::Pop, "pop", 0(0): op_pop);
+    "&" => (Operator::BitAnd, "band", 1(2): op_band);
+
+    "\\" => (Operator::BitOr, "bor", 1(2): op_bor);
+
+    "^" => (Operator::BitXor, "bxor", 1(2): op_bxor);
+
+    "<<" => (Operator::ShiftLeft, "shl", 1(2): op_shl);
+
+    ">>" => (Operator::ShiftRight, "shr", 1(2): op_shr);
+
+    ";" => (Operator::Pop, "pop", 0(1): op_pop);
 
     ":" => (Operator::Swap, "swp", 2(2): op_swap);
 
     "." => (Operator::Dup, "dup", 2(1): op_dup);
 
-    "?" => (Operator::Call, "call", 0(0): |_: Scope| {
+    "?" => (Operator::Call, "call", _(_): |_: Scope| {
         night_err!(ContextFail, "An internal error occurred, this should not have been called.")
     });
 }
 
 define_builtins! {
-    "print" => (Builtin::Print, 0(0): print);
+    "print" => (Builtin::Print, 0(1): print);
 
     "inc" => (Builtin::Inc, 1(1): inc);
 
@@ -162,9 +212,9 @@ define_builtins! {
 
     "over" => (Builtin::Over, 3(2): over);
 
-    "rot" => (Builtin::Rot, 0(0): rot);
+    "rot" => (Builtin::Rot, _(_): rot);
 
-    "rotr" => (Builtin::RotRight, 0(0): rotr);
+    "rotr" => (Builtin::RotRight, _(_): rotr);
 
     "dupd" => (Builtin::Dupd, 3(2): dup_dip);
 
@@ -174,7 +224,7 @@ define_builtins! {
 
     "pop3" => (Builtin::Pop3, 0(3): pop3);
 
-    "nip" => (Builtin::Popd, 0(0): pop_dip);
+    "nip" => (Builtin::Popd, 1(2): pop_dip);
 
     "dup2" => (Builtin::Dup2, 4(2): dup2);
 
@@ -194,13 +244,167 @@ define_builtins! {
 
     "f64" => (Builtin::CastToFloat, 1(1): cast_to_float);
 
-    "curry" => (Builtin::Curry, 0(0): curry);
+    "bnot" => (Builtin::BitNot, 1(1): bit_not);
+
+    "popcount" => (Builtin::PopCount, 1(1): popcount);
+
+    "test_bit" => (Builtin::TestBit, 1(2): test_bit);
+
+    "len" => (Builtin::Len, 1(1): len);
+
+    "slice" => (Builtin::Slice, 1(3): slice);
+
+    "index_of" => (Builtin::IndexOf, 1(2): index_of);
+
+    "split" => (Builtin::Split, 1(2): split);
+
+    "join" => (Builtin::Join, 1(2): join);
+
+    "upper" => (Builtin::Upper, 1(1): upper);
+
+    "lower" => (Builtin::Lower, 1(1): lower);
+
+    "trim" => (Builtin::Trim, 1(1): trim);
+
+    "replace" => (Builtin::Replace, 1(3): replace);
+
+    "starts_with" => (Builtin::StartsWith, 1(2): starts_with);
+
+    "ends_with" => (Builtin::EndsWith, 1(2): ends_with);
+
+    "chars" => (Builtin::Chars, 1(1): chars);
+
+    "ord" => (Builtin::Ord, 1(1): ord);
+
+    "chr" => (Builtin::Chr, 1(1): chr);
+
+    "repeat" => (Builtin::Repeat, 1(2): repeat);
+
+    "format" => (Builtin::Format, _(_): format);
+
+    "get" => (Builtin::Get, 1(2): get);
+
+    "set" => (Builtin::Set, 1(3): set);
+
+    "has" => (Builtin::Has, 1(2): has);
+
+    "del" => (Builtin::Del, 1(2): del);
+
+    "keys" => (Builtin::Keys, 1(1): keys);
+
+    "values" => (Builtin::Values, 1(1): values);
+
+    "entries" => (Builtin::Entries, 1(1): entries);
+
+    "default" => (Builtin::Default, 1(2): default);
+
+    "some?" => (Builtin::IsSome, 1(1): is_some);
+
+    "parse" => (Builtin::Parse, 1(1): parse);
+
+    "curry" => (Builtin::Curry, 1(2): curry);
+
+    "bind" => (Builtin::Bind, 1(2): bind);
+
+    "quot_len" => (Builtin::QuotLen, 1(1): quot_len);
+
+    "quot_nth" => (Builtin::QuotNth, 1(2): quot_nth);
+
+    "compose" => (Builtin::Compose, _(_): compose);
+
+    "prepose" => (Builtin::Prepose, 1(2): prepose);
+
+    "quot_append" => (Builtin::QuotAppend, 1(2): quot_append);
+
+    ">quot" => (Builtin::ToQuot, 1(1): to_quot);
+
+    "quot>" => (Builtin::FromQuot, 1(1): from_quot);
+
+    "unquote" => (Builtin::Unquote, _(_): unquote);
+
+    "struct_new" => (Builtin::StructNew, _(_): struct_new);
+
+    "get_field" => (Builtin::GetField, 1(3): get_field);
+
+    "set_field" => (Builtin::SetField, 1(4): set_field);
+
+    "is_a" => (Builtin::IsA, 1(2): is_a);
+
+    "range" => (Builtin::Range, 1(2): range);
+
+    "range_by" => (Builtin::RangeBy, 1(3): range_by);
+
+    "read_file" => (Builtin::ReadFile, 1(1): read_file);
+
+    "write_file" => (Builtin::WriteFile, 0(2): write_file);
+
+    "append_file" => (Builtin::AppendFile, 0(2): append_file);
+
+    "read_lines" => (Builtin::ReadLines, 1(1): read_lines);
+
+    "exists" => (Builtin::Exists, 1(1): exists);
+
+    "list_dir" => (Builtin::ListDir, 1(1): list_dir);
+
+    "mkdir" => (Builtin::MakeDir, 0(1): make_dir);
+
+    "remove" => (Builtin::Remove, 0(1): remove);
+
+    "write" => (Builtin::Write, 0(1): write);
+
+    "eprint" => (Builtin::EPrint, 0(1): eprint);
+
+    "read_line" => (Builtin::ReadLine, 1(0): read_line);
+
+    "read_all" => (Builtin::ReadAll, 1(0): read_all);
+
+    "args" => (Builtin::Args, 1(0): args);
+
+    "env" => (Builtin::Env, 1(1): env);
+
+    "exit" => (Builtin::Exit, 0(1): exit);
+
+    "script_path" => (Builtin::ScriptPath, 1(0): script_path);
+
+    "rand" => (Builtin::Rand, 1(0): rand);
+
+    "rand_int" => (Builtin::RandInt, 1(2): rand_int);
+
+    "shuffle" => (Builtin::Shuffle, 1(1): shuffle);
 
-    "bind" => (Builtin::Bind, 0(0): bind);
+    "choice" => (Builtin::Choice, 1(1): choice);
+
+    "seed" => (Builtin::Seed, 0(1): seed);
+
+    "now" => (Builtin::Now, 1(0): now);
+
+    "monotonic" => (Builtin::Monotonic, 1(0): monotonic);
+
+    "sleep" => (Builtin::Sleep, 0(1): sleep);
+
+    "fmt_time" => (Builtin::FmtTime, 1(2): fmt_time);
+
+    "parse_time" => (Builtin::ParseTime, 1(2): parse_time);
+
+    "json_parse" => (Builtin::JsonParse, 1(1): json_parse);
+
+    "json_str" => (Builtin::JsonStr, 1(1): json_str);
+
+    "csv_parse" => (Builtin::CsvParse, 1(1): csv_parse);
+
+    "csv_parse_with" => (Builtin::CsvParseWith, 1(2): csv_parse_with);
+
+    "csv_str" => (Builtin::CsvStr, 1(1): csv_str);
+
+    "csv_str_with" => (Builtin::CsvStrWith, 1(2): csv_str_with);
 }
 
 fn op_add(_: Scope, left: Value, right: Value) -> Status<Value> {
-    left + right
+    if left.is_str() && right.is_str() {
+        Ok(Value::from(left.as_str()? + &right.as_str()?))
+    } else {
+        left + right
+    }
 }
 
 fn op_sub(_: Scope, left: Value, right: Value) -> Status<Value> {
@@ -219,11 +423,11 @@ fn op_mod(_: Scope, left: Value, right: Value) -> Status<Value> {
     left % right
 }
 
-fn op_eq(_: Scope, left: Value, right: Value) -> Status<Value> {
+fn op_eq(_: Scope, left: StackVal, right: StackVal) -> Status<Value> {
     Ok(Value::from(left == right))
 }
 
-fn op_neq(_: Scope, left: Value, right: Value) -> Status<Value> {
+fn op_neq(_: Scope, left: StackVal, right: StackVal) -> Status<Value> {
     Ok(Value::from(left != right))
 }
 
@@ -275,8 +479,40 @@ fn op_not(_: Scope, value: Value) -> Status<Value> {
     Ok(Value::from(!value.as_bool()?))
 }
 
-fn op_pop(scope: Scope) -> Status {
-    scope.borrow_mut().pop()?;
+fn op_band(_: Scope, left: Value, right: Value) -> Status<Value> {
+    Ok(Value::from(left.as_bits()? & right.as_bits()?))
+}
+
+fn op_bor(_: Scope, left: Value, right: Value) -> Status<Value> {
+    Ok(Value::from(left.as_bits()? | right.as_bits()?))
+}
+
+fn op_bxor(_: Scope, left: Value, right: Value) -> Status<Value> {
+    Ok(Value::from(left.as_bits()? ^ right.as_bits()?))
+}
+
+fn op_shl(_: Scope, left: Value, right: Value) -> Status<Value> {
+    let left = left.as_bits()?;
+    Ok(Value::from(left << shift_amount(right)?))
+}
+
+fn op_shr(_: Scope, left: Value, right: Value) -> Status<Value> {
+    let left = left.as_bits()?;
+    Ok(Value::from(left >> shift_amount(right)?))
+}
+
+// Shifting an i64 by 64 or more bits would overflow, so those amounts are rejected
+fn shift_amount(value: Value) -> Status<u32> {
+    match value.as_bits()? {
+        n @ 0..=63 => Ok(n as u32),
+        n => night_err!(
+            Runtime,
+            format!("Cannot shift by {n} bits, must be within 0..64.")
+        ),
+    }
+}
+
+fn op_pop(_: Scope, _: StackVal) -> Status {
     Ok(())
 }
 
@@ -298,9 +534,9 @@ fn op_dup(scope: Scope) -> Status {
     Ok(())
 }
 
-fn print(scope: Scope) -> Status {
-    let v = scope.borrow_mut().pop()?;
-    println!("{v}");
+fn print(scope: Scope, v: StackVal) -> Status {
+    let out = scope.borrow().out();
+    writeln!(out.borrow_mut(), "{v}")?;
     Ok(())
 }
 
@@ -385,11 +621,8 @@ fn pop3(scope: Scope) -> Status {
     Ok(())
 }
 
-fn pop_dip(scope: Scope) -> Status {
-    let mut s = scope.borrow_mut();
-    let (_, top) = s.pop2()?;
-    s.push(top);
-    Ok(())
+fn pop_dip(_: Scope, _: StackVal, top: StackVal) -> Status<StackVal> {
+    Ok(top)
 }
 
 fn dup2(scope: Scope) -> Status {
@@ -445,22 +678,691 @@ fn cast_to_float(_: Scope, value: Value) -> Status<Value> {
     Ok(Value::from(value.as_float()?))
 }
 
-fn curry(scope: Scope) -> Status {
+fn bit_not(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(!value.as_bits()?))
+}
+
+fn popcount(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(value.as_bits()?.count_ones() as i64))
+}
+
+fn test_bit(_: Scope, value: Value, bit: Value) -> Status<Value> {
+    let value = value.as_bits()?;
+    Ok(Value::from(value >> shift_amount(bit)? & 1 == 1))
+}
+
+fn len(_: Scope, value: Value) -> Status<Value> {
+    if value.is_array() {
+        Ok(Value::from(value.as_array()?.len() as i64))
+    } else if value.is_map() {
+        Ok(Value::from(value.as_map()?.len() as i64))
+    } else {
+        Ok(Value::from(value.as_str()?.chars().count() as i64))
+    }
+}
+
+fn slice(_: Scope, value: Value, start: Value, end: Value) -> Status<Value> {
+    let chars = value.as_str()?.chars().collect::<Vec<_>>();
+    let (start, end) = (start.as_int()?, end.as_int()?);
+    if start < 0 || end < start || end as usize > chars.len() {
+        return night_err!(
+            Runtime,
+            format!(
+                "Cannot slice {start}..{end} from a string of length {}.",
+                chars.len()
+            )
+        );
+    }
+    Ok(Value::from(
+        chars[start as usize..end as usize]
+            .iter()
+            .collect::<String>(),
+    ))
+}
+
+fn index_of(_: Scope, value: Value, needle: Value) -> Status<Value> {
+    let value = value.as_str()?;
+    let index = value
+        .find(&needle.as_str()?)
+        .map(|i| value[..i].chars().count() as i64);
+    Ok(Value::from(index))
+}
+
+fn split(_: Scope, value: Value, sep: Value) -> Status<Value> {
+    let value = value.as_str()?;
+    let sep = sep.as_str()?;
+    // Splitting on the empty string would otherwise produce empty strings at either end
+    if sep.is_empty() {
+        return Ok(Value::from(str_chars(&value)));
+    }
+    Ok(Value::from(
+        value.split(&sep).map(Value::from).collect::<Vec<_>>(),
+    ))
+}
+
+fn join(_: Scope, values: Value, sep: Value) -> Status<Value> {
+    let sep = sep.as_str()?;
+    let parts = values
+        .as_array()?
+        .iter()
+        .map(Value::to_string)
+        .collect::<Vec<_>>();
+    Ok(Value::from(parts.join(&sep)))
+}
+
+fn upper(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(value.as_str()?.to_uppercase()))
+}
+
+fn lower(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(value.as_str()?.to_lowercase()))
+}
+
+fn trim(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(value.as_str()?.trim()))
+}
+
+fn replace(_: Scope, value: Value, from: Value, to: Value) -> Status<Value> {
+    Ok(Value::from(
+        value.as_str()?.replace(&from.as_str()?, &to.as_str()?),
+    ))
+}
+
+fn starts_with(_: Scope, value: Value, prefix: Value) -> Status<Value> {
+    Ok(Value::from(value.as_str()?.starts_with(&prefix.as_str()?)))
+}
+
+fn ends_with(_: Scope, value: Value, suffix: Value) -> Status<Value> {
+    Ok(Value::from(value.as_str()?.ends_with(&suffix.as_str()?)))
+}
+
+fn chars(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(str_chars(&value.as_str()?)))
+}
+
+fn str_chars(s: &str) -> Vec<Value> {
+    s.chars().map(|c| Value::from(c.to_string())).collect()
+}
+
+fn ord(_: Scope, value: Value) -> Status<Value> {
+    let value = value.as_str()?;
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Value::from(c as i64)),
+        _ => night_err!(
+            Runtime,
+            format!("'ord' expects a single character, got '{value}'.")
+        ),
+    }
+}
+
+fn chr(_: Scope, value: Value) -> Status<Value> {
+    let code = value.as_int()?;
+    u32::try_from(code)
+        .ok()
+        .and_then(char::from_u32)
+        .map(|c| Value::from(c.to_string()))
+        .ok_or(NightError::Runtime(format!(
+            "{code} is not a valid unicode codepoint."
+        )))
+}
+
+fn repeat(_: Scope, value: Value, count: Value) -> Status<Value> {
+    let value = value.as_str()?;
+    match count.as_int()? {
+        n if n < 0 => night_err!(Runtime, "'repeat' can only take a positive integer."),
+        n => Ok(Value::from(value.repeat(n as usize))),
+    }
+}
+
+fn format(scope: Scope) -> Status {
     let mut s = scope.borrow_mut();
-    let (op, block) = s.pop2()?;
-    s.push(StackVal::Function(Rc::new(CurriedFunc::new(
+    let template = s.pop_value()?.as_str()?;
+    let mut args = (0..format::arg_count(&template)?)
+        .map(|_| s.pop_value())
+        .collect::<Status<Vec<_>>>()?;
+    args.reverse();
+    let formatted = format::format(&template, args)?;
+    s.push_value(Value::from(formatted));
+    Ok(())
+}
+
+fn get(_: Scope, map: Value, key: Value) -> Status<Value> {
+    Ok(Value::from(map.as_map()?.remove(&key)))
+}
+
+fn set(_: Scope, map: Value, key: Value, value: Value) -> Status<Value> {
+    let mut map = map.as_map()?;
+    map.insert(key, value);
+    Ok(Value::from(map))
+}
+
+fn has(_: Scope, map: Value, key: Value) -> Status<Value> {
+    Ok(Value::from(map.as_map()?.contains(&key)))
+}
+
+fn del(_: Scope, map: Value, key: Value) -> Status<Value> {
+    let mut map = map.as_map()?;
+    map.remove(&key);
+    Ok(Value::from(map))
+}
+
+fn keys(_: Scope, map: Value) -> Status<Value> {
+    let keys = map
+        .as_map()?
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+    Ok(Value::from(keys))
+}
+
+fn values(_: Scope, map: Value) -> Status<Value> {
+    let values = map
+        .as_map()?
+        .into_iter()
+        .map(|(_, v)| v)
+        .collect::<Vec<_>>();
+    Ok(Value::from(values))
+}
+
+fn entries(_: Scope, map: Value) -> Status<Value> {
+    let entries = map
+        .as_map()?
+        .into_iter()
+        .map(|(k, v)| Value::from(vec![k, v]))
+        .collect::<Vec<_>>();
+    Ok(Value::from(entries))
+}
+
+fn default(_: Scope, value: Value, default: Value) -> Status<Value> {
+    Ok(if value.is_nil() { default } else { value })
+}
+
+fn is_some(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(!value.is_nil()))
+}
+
+fn parse(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(
+        value::parse_number(value.as_str()?.trim()).ok(),
+    ))
+}
+
+fn curry(_: Scope, op: StackVal, block: StackVal) -> Status<StackVal> {
+    Ok(StackVal::Function(Rc::new(CurriedFunc::new(
         op,
         block.as_fn()?,
-    ))));
-    Ok(())
+    ))))
 }
 
-fn bind(scope: Scope) -> Status {
-    let mut s = scope.borrow_mut();
-    let (block1, block2) = s.pop2()?;
-    s.push(StackVal::Function(Rc::new(ComposedFunc::new(
+fn bind(_: Scope, block1: StackVal, block2: StackVal) -> Status<StackVal> {
+    Ok(StackVal::Function(Rc::new(ComposedFunc::new(
         block1.as_fn()?,
         block2.as_fn()?,
-    ))));
+    ))))
+}
+
+fn quot_len(_: Scope, quot: StackVal) -> Status<Value> {
+    Ok(Value::from(
+        function::words(quot.as_fn()?.as_ref()).len() as i64
+    ))
+}
+
+fn quot_nth(_: Scope, quot: StackVal, n: Value) -> Status<StackVal> {
+    let mut elements = function::elements(quot.as_fn()?.as_ref());
+    let len = elements.len();
+    let n = n.as_int()?;
+    if n < 0 || n as usize >= len {
+        return night_err!(
+            Runtime,
+            format!("Index {n} is out of bounds for a function of length {len}.")
+        );
+    }
+
+    Ok(elements.swap_remove(n as usize))
+}
+
+fn compose(scope: Scope) -> Status {
+    let mut s = scope.borrow_mut();
+    let n = s.pop_value()?.as_int()?;
+    if n < 1 {
+        return night_err!(Runtime, "'compose' needs at least one function.");
+    }
+
+    let mut blocks = (0..n)
+        .map(|_| s.pop()?.as_fn())
+        .collect::<Status<Vec<_>>>()?;
+    let mut composed = blocks.pop().unwrap();
+    while let Some(block) = blocks.pop() {
+        composed = Rc::new(ComposedFunc::new(composed, block));
+    }
+    s.push(StackVal::Function(composed));
+    Ok(())
+}
+
+fn prepose(_: Scope, block1: StackVal, block2: StackVal) -> Status<StackVal> {
+    Ok(StackVal::Function(Rc::new(ComposedFunc::new(
+        block2.as_fn()?,
+        block1.as_fn()?,
+    ))))
+}
+
+fn quot_append(_: Scope, block: StackVal, item: StackVal) -> Status<StackVal> {
+    Ok(StackVal::Function(Rc::new(ComposedFunc::new(
+        block.as_fn()?,
+        function::quote_one(item),
+    ))))
+}
+
+fn to_quot(_: Scope, items: Value) -> Status<StackVal> {
+    let items = items.as_array()?.into_iter().map(StackVal::Value).collect();
+    Ok(StackVal::Function(function::quote(items)))
+}
+
+// Arrays only hold values, so quotations containing words or blocks need `unquote` instead
+fn from_quot(_: Scope, quot: StackVal) -> Status<Value> {
+    let items = function::elements(quot.as_fn()?.as_ref())
+        .into_iter()
+        .map(|element| match element {
+            StackVal::Value(v) => Ok(v),
+            f => night_err!(
+                UnsupportedType,
+                format!("Cannot convert a quotation containing '{f}' to an array.")
+            ),
+        })
+        .collect::<Status<Vec<_>>>()?;
+    Ok(Value::from(items))
+}
+
+fn unquote(scope: Scope) -> Status {
+    let mut s = scope.borrow_mut();
+    for element in function::elements(s.pop()?.as_fn()?.as_ref()) {
+        s.push(element);
+    }
+    Ok(())
+}
+
+fn struct_new(scope: Scope) -> Status {
+    let mut s = scope.borrow_mut();
+    let name = s.pop_value()?.as_str()?;
+    let fields = s
+        .pop_value()?
+        .as_array()?
+        .into_iter()
+        .map(Value::as_str)
+        .collect::<Status<Vec<_>>>()?;
+    let mut values = (0..fields.len())
+        .map(|_| s.pop_value())
+        .collect::<Status<Vec<_>>>()?;
+    values.reverse();
+    s.push_value(Value::from(Struct::new(name.into(), fields.into(), values)));
+    Ok(())
+}
+
+// Structs can only be used by the words generated for their type
+fn expect_struct(value: Value, name: &str) -> Status<Struct> {
+    match value.clone().as_struct() {
+        Ok(s) if s.name() == name => Ok(s),
+        _ => night_err!(
+            UnsupportedType,
+            format!("Expected a '{name}', got '{}'.", value.repr())
+        ),
+    }
+}
+
+fn get_field(_: Scope, value: Value, name: Value, field: Value) -> Status<Value> {
+    let value = expect_struct(value, &name.as_str()?)?;
+    value.get(&field.as_str()?).cloned()
+}
+
+fn set_field(scope: Scope) -> Status {
+    let mut s = scope.borrow_mut();
+    let field = s.pop_value()?.as_str()?;
+    let name = s.pop_value()?.as_str()?;
+    let x = s.pop_value()?;
+    let mut value = expect_struct(s.pop_value()?, &name)?;
+    value.set(&field, x)?;
+    s.push_value(Value::from(value));
+    Ok(())
+}
+
+fn is_a(_: Scope, value: Value, name: Value) -> Status<Value> {
+    let name = name.as_str()?;
+    Ok(Value::from(
+        value.as_struct().is_ok_and(|s| s.name() == name),
+    ))
+}
+
+fn range(scope: Scope, start: Value, end: Value) -> Status<Value> {
+    range_by(scope, start, end, Value::from(1))
+}
+
+// Like Rust's ranges, `end` is exclusive. A negative step counts down.
+fn range_by(_: Scope, start: Value, end: Value, step: Value) -> Status<Value> {
+    let (start, end, step) = (start.as_int()?, end.as_int()?, step.as_int()?);
+    if step == 0 {
+        return night_err!(Runtime, "'range_by' cannot take a step of 0.");
+    }
+
+    let mut values = Vec::new();
+    let mut n = start;
+    while (step > 0 && n < end) || (step < 0 && n > end) {
+        values.push(Value::from(n));
+        n += step;
+    }
+    Ok(Value::from(values))
+}
+
+// Keeps the OS error message, so the failing path and reason are both reported
+// The resulting `IOError` stops the script and comes back from `Night::exec` for the caller to handle
+fn io_err(action: &str, path: &str) -> impl FnOnce(io::Error) -> NightError {
+    let msg = format!("Could not {action} '{path}'");
+    move |e| NightError::Io(format!("{msg}: {e}."))
+}
+
+fn read_file(_: Scope, path: Value) -> Status<Value> {
+    let path = path.as_str()?;
+    let contents = fs::read_to_string(&path).map_err(io_err("read", &path))?;
+    Ok(Value::from(contents))
+}
+
+fn write_file(scope: Scope) -> Status {
+    let mut s = scope.borrow_mut();
+    let path = s.pop_value()?.as_str()?;
+    let contents = s.pop_value()?.as_str()?;
+    fs::write(&path, contents).map_err(io_err("write to", &path))
+}
+
+fn append_file(scope: Scope) -> Status {
+    let mut s = scope.borrow_mut();
+    let path = s.pop_value()?.as_str()?;
+    let contents = s.pop_value()?.as_str()?;
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .map_err(io_err("append to", &path))
+}
+
+fn read_lines(_: Scope, path: Value) -> Status<Value> {
+    let path = path.as_str()?;
+    let contents = fs::read_to_string(&path).map_err(io_err("read", &path))?;
+    let lines = contents.lines().map(Value::from).collect::<Vec<_>>();
+    Ok(Value::from(lines))
+}
+
+fn exists(_: Scope, path: Value) -> Status<Value> {
+    Ok(Value::from(Path::new(&path.as_str()?).exists()))
+}
+
+// Entries are sorted, since the order returned by the OS is unspecified
+fn list_dir(_: Scope, path: Value) -> Status<Value> {
+    let path = path.as_str()?;
+    let mut names = fs::read_dir(&path)
+        .and_then(|dir| {
+            dir.map(|e| e.map(|e| e.file_name().to_string_lossy().into_owned()))
+                .collect::<io::Result<Vec<_>>>()
+        })
+        .map_err(io_err("list", &path))?;
+    names.sort();
+    Ok(Value::from(names.into_iter().map(Value::from).collect::<Vec<_>>()))
+}
+
+// Creates any missing parent directories as well
+fn make_dir(_: Scope, path: Value) -> Status {
+    let path = path.as_str()?;
+    fs::create_dir_all(&path).map_err(io_err("create", &path))
+}
+
+fn remove(_: Scope, path: Value) -> Status {
+    let path = path.as_str()?;
+    if Path::new(&path).is_dir() {
+        fs::remove_dir_all(&path)
+    } else {
+        fs::remove_file(&path)
+    }
+    .map_err(io_err("remove", &path))
+}
+
+// Flushed right away, since stdout is only flushed on newlines
+fn write(scope: Scope, v: StackVal) -> Status {
+    let out = scope.borrow().out();
+    let mut out = out.borrow_mut();
+    write!(out, "{v}")?;
+    out.flush()?;
+    Ok(())
+}
+
+fn eprint(scope: Scope, v: StackVal) -> Status {
+    let err = scope.borrow().err();
+    writeln!(err.borrow_mut(), "{v}")?;
+    Ok(())
+}
+
+fn read_line(scope: Scope) -> Status<Value> {
+    let input = scope.borrow().input();
+    Ok(Value::from(utils::read_input_line(&input)?))
+}
+
+fn read_all(scope: Scope) -> Status<Value> {
+    let input = scope.borrow().input();
+    let mut all = String::new();
+    input
+        .borrow_mut()
+        .read_to_string(&mut all)
+        .map_err(|e| NightError::Io(format!("Could not read input: {e}.")))?;
+    Ok(Value::from(all))
+}
+
+fn args(scope: Scope) -> Status<Value> {
+    let process = scope.borrow().process();
+    let args = process.args.iter().map(|a| Value::from(a.as_str()));
+    Ok(Value::from(args.collect::<Vec<_>>()))
+}
+
+fn env(scope: Scope, name: Value) -> Status<Value> {
+    let process = scope.borrow().process();
+    Ok(Value::from(process.env.get(&name.as_str()?).cloned()))
+}
+
+// Stops the program by handing the code back through `Night::exec`, for the CLI to exit with.
+// Output is flushed first, as exiting skips any buffered writes.
+fn exit(scope: Scope, code: Value) -> Status {
+    let code = code.as_int()?;
+    let Ok(code) = i32::try_from(code) else {
+        return night_err!(Runtime, format!("Exit code {code} is out of range."));
+    };
+    let s = scope.borrow();
+    s.out().borrow_mut().flush()?;
+    s.err().borrow_mut().flush()?;
+    Err(NightError::Exit(code))
+}
+
+fn script_path(scope: Scope) -> Status<Value> {
+    Ok(Value::from(scope.borrow().process().script_path.clone()))
+}
+
+// In [0, 1)
+fn rand(scope: Scope) -> Status<Value> {
+    let rng = scope.borrow().rng();
+    let n: f64 = rng.borrow_mut().gen();
+    Ok(Value::from(n))
+}
+
+// Both bounds are inclusive, so `1 6 rand_int` rolls a die
+fn rand_int(scope: Scope, lo: Value, hi: Value) -> Status<Value> {
+    let (lo, hi) = (lo.as_int()?, hi.as_int()?);
+    if lo > hi {
+        return night_err!(Runtime, format!("Cannot pick a number between {lo} and {hi}."));
+    }
+    let rng = scope.borrow().rng();
+    let n = rng.borrow_mut().gen_range(lo..=hi);
+    Ok(Value::from(n))
+}
+
+fn shuffle(scope: Scope, array: Value) -> Status<Value> {
+    let mut values = array.as_array()?;
+    let rng = scope.borrow().rng();
+    values.shuffle(&mut *rng.borrow_mut());
+    Ok(Value::from(values))
+}
+
+fn choice(scope: Scope, array: Value) -> Status<Value> {
+    let values = array.as_array()?;
+    let rng = scope.borrow().rng();
+    let picked = values.choose(&mut *rng.borrow_mut()).cloned();
+    Ok(Value::from(picked))
+}
+
+fn seed(scope: Scope, seed: Value) -> Status {
+    let seed = seed.as_int()?;
+    let rng = scope.borrow().rng();
+    *rng.borrow_mut() = StdRng::seed_from_u64(seed as u64);
+    Ok(())
+}
+
+fn now(_: Scope) -> Status<Value> {
+    Ok(Value::from(time::now()))
+}
+
+fn monotonic(_: Scope) -> Status<Value> {
+    Ok(Value::from(time::monotonic_ns()))
+}
+
+fn sleep(_: Scope, secs: Value) -> Status {
+    let secs = secs.as_float()?;
+    if !(secs >= 0.0 && secs.is_finite()) {
+        return night_err!(Runtime, format!("Cannot sleep for {secs} seconds."));
+    }
+    std::thread::sleep(std::time::Duration::from_secs_f64(secs));
     Ok(())
 }
+
+// Fractions of a second are dropped
+fn fmt_time(_: Scope, secs: Value, fmt: Value) -> Status<Value> {
+    let secs = secs.as_float()?.floor() as i64;
+    Ok(Value::from(time::format(secs, &fmt.as_str()?)?))
+}
+
+fn parse_time(_: Scope, s: Value, fmt: Value) -> Status<Value> {
+    Ok(Value::from(time::parse(&s.as_str()?, &fmt.as_str()?)?))
+}
+
+fn json_parse(_: Scope, s: Value) -> Status<Value> {
+    json::parse(&s.as_str()?)
+}
+
+fn json_str(_: Scope, value: Value) -> Status<Value> {
+    Ok(Value::from(value.to_json()?))
+}
+
+struct CsvOptions {
+    delim: char,
+    // Whether the first row names the fields, making each row a map
+    header: bool,
+}
+
+// Read from a map like `#{ :delim ";" :header true }`, any missing option keeps its default
+fn csv_options(opts: Value, header: bool) -> Status<CsvOptions> {
+    let opts = opts.as_map()?;
+    let delim = match opts.get(&Value::from("delim")) {
+        Some(d) => {
+            let d = d.clone().as_str()?;
+            let mut chars = d.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if !matches!(c, '"' | '\n' | '\r') => c,
+                _ => {
+                    return night_err!(
+                        Runtime,
+                        format!("'{d}' is not a valid CSV delimiter, expected one character.")
+                    )
+                }
+            }
+        }
+        None => ',',
+    };
+    let header = match opts.get(&Value::from("header")) {
+        Some(h) => h.clone().as_bool()?,
+        None => header,
+    };
+    Ok(CsvOptions { delim, header })
+}
+
+fn csv_parse(scope: Scope, s: Value) -> Status<Value> {
+    csv_parse_with(scope, s, Value::from(Map::new()))
+}
+
+// With a header, short rows are padded with `nil`
+fn csv_parse_with(_: Scope, s: Value, opts: Value) -> Status<Value> {
+    let opts = csv_options(opts, false)?;
+    let rows = csv::parse(&s.as_str()?, opts.delim)?;
+    let to_values = |row: Vec<String>| row.into_iter().map(Value::from).collect::<Vec<_>>();
+    if !opts.header {
+        return Ok(Value::from(
+            rows.into_iter().map(to_values).map(Value::from).collect::<Vec<_>>(),
+        ));
+    }
+
+    let mut rows = rows.into_iter();
+    let header = rows.next().map(to_values).unwrap_or_default();
+    let mut maps = Vec::new();
+    for (i, row) in rows.enumerate() {
+        if row.len() > header.len() {
+            return night_err!(
+                Runtime,
+                format!(
+                    "CSV row {} has {} fields, but the header only has {}.",
+                    i + 2,
+                    row.len(),
+                    header.len()
+                )
+            );
+        }
+        let mut map = Map::new();
+        let mut row = row.into_iter();
+        for key in &header {
+            map.insert(key.clone(), Value::from(row.next()));
+        }
+        maps.push(Value::from(map));
+    }
+    Ok(Value::from(maps))
+}
+
+fn csv_str(scope: Scope, rows: Value) -> Status<Value> {
+    csv_str_with(scope, rows, Value::from(Map::new()))
+}
+
+// Fields are written with `Display`, except `nil` which becomes an empty field. Rows of maps use
+// the keys of the first row, written as a header unless it is turned off.
+fn csv_str_with(_: Scope, rows: Value, opts: Value) -> Status<Value> {
+    let opts = csv_options(opts, true)?;
+    let rows = rows.as_array()?;
+    let field = |v: Option<&Value>| match v {
+        Some(v) if !v.is_nil() => v.to_string(),
+        _ => String::new(),
+    };
+
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    if rows.first().is_some_and(Value::is_map) {
+        let rows = rows.into_iter().map(Value::as_map).collect::<Status<Vec<_>>>()?;
+        // Every key of every row, in the order first seen, so rows with extra keys lose nothing
+        let mut keys = Vec::new();
+        for (k, _) in rows.iter().flat_map(Map::iter) {
+            if !keys.contains(k) {
+                keys.push(k.clone());
+            }
+        }
+        if opts.header {
+            lines.push(keys.iter().map(|k| field(Some(k))).collect());
+        }
+        for row in rows {
+            lines.push(keys.iter().map(|k| field(row.get(k))).collect());
+        }
+    } else {
+        for row in rows {
+            lines.push(row.as_array()?.iter().map(|v| field(Some(v))).collect());
+        }
+    }
+    Ok(Value::from(csv::write(&lines, opts.delim)))
+}
@@ -30,6 +30,16 @@ pub enum Operator {
     LessEq,
     /// 1 ~ -- 0
     Not,
+    /// 6 3 & -- 2
+    BitAnd,
+    /// 6 3 \ -- 7
+    BitOr,
+    /// 6 3 ^ -- 5
+    BitXor,
+    /// 1 4 << -- 16
+    ShiftLeft,
+    /// 16 2 >> -- 4
+    ShiftRight,
     /// 5 4 6 ; -- 5 4
     Pop,
     /// 3 2 : -- 2 3
@@ -92,12 +102,210 @@ pub enum Builtin {
     CastToInt,
     /// f32 ( num -- f32 )
     CastToFloat,
+    /// bnot ( i64 -- i64 )
+    BitNot,
+    /// popcount ( i64 -- i64 )
+    PopCount,
+    /// test_bit ( i64 bit -- bool )
+    TestBit,
+
+    // Strings
+    /// len ( str/array/map -- n )
+    Len,
+    /// slice ( str start end -- str )
+    Slice,
+    /// index_of ( str needle -- n/nil )
+    IndexOf,
+    /// split ( str sep -- [str] )
+    Split,
+    /// join ( [a] sep -- str )
+    Join,
+    /// upper ( str -- str )
+    Upper,
+    /// lower ( str -- str )
+    Lower,
+    /// trim ( str -- str )
+    Trim,
+    /// replace ( str from to -- str )
+    Replace,
+    /// starts_with ( str prefix -- bool )
+    StartsWith,
+    /// ends_with ( str suffix -- bool )
+    EndsWith,
+    /// chars ( str -- [str] )
+    Chars,
+    /// ord ( str -- i64 )
+    Ord,
+    /// chr ( i64 -- str )
+    Chr,
+    /// repeat ( str n -- str )
+    Repeat,
+    /// format ( ..args template -- str )
+    Format,
+
+    // Maps
+    /// get ( map key -- value/nil )
+    Get,
+    /// set ( map key value -- map )
+    Set,
+    /// has ( map key -- bool )
+    Has,
+    /// del ( map key -- map )
+    Del,
+    /// keys ( map -- [key] )
+    Keys,
+    /// values ( map -- [value] )
+    Values,
+    /// entries ( map -- [[key value]] )
+    Entries,
+
+    // Nil
+    /// default ( x/nil d -- x/d )
+    Default,
+    /// some? ( x/nil -- bool )
+    IsSome,
+    /// parse ( str -- num/nil )
+    Parse,
 
     // Built-in combinators
     /// curry ( op f -- { op ...f } )
     Curry,
     /// bind ( f1 f2 -- { ...f1 ...f2 } )
     Bind,
+    /// quot_len ( f -- n )
+    QuotLen,
+    /// quot_nth ( f n -- x )
+    QuotNth,
+    /// compose ( f1 ... fn n -- { ...f1 ... ...fn } )
+    Compose,
+    /// prepose ( f1 f2 -- { ...f2 ...f1 } )
+    Prepose,
+    /// quot_append ( f x -- { ...f x } )
+    QuotAppend,
+    /// >quot ( [x1 ... xn] -- { x1 ... xn } )
+    ToQuot,
+    /// quot> ( { x1 ... xn } -- [x1 ... xn] )
+    FromQuot,
+    /// unquote ( { x1 ... xn } -- x1 ... xn )
+    Unquote,
+
+    // Structs
+    /// struct_new ( x1 ... xn [field1 ... fieldn] name -- struct )
+    StructNew,
+    /// get_field ( struct name field -- x )
+    GetField,
+    /// set_field ( struct x name field -- struct )
+    SetField,
+    /// is_a ( x name -- bool )
+    IsA,
+
+    // Iteration
+    /// range ( start end -- [n] )
+    Range,
+    /// range_by ( start end step -- [n] )
+    RangeBy,
+
+    // Files
+    /// read_file ( path -- str )
+    ReadFile,
+    /// write_file ( str path -- )
+    WriteFile,
+    /// append_file ( str path -- )
+    AppendFile,
+    /// read_lines ( path -- [str] )
+    ReadLines,
+    /// exists ( path -- bool )
+    Exists,
+    /// list_dir ( path -- [str] )
+    ListDir,
+    /// mkdir ( path -- )
+    MakeDir,
+    /// remove ( path -- )
+    Remove,
+
+    // Standard streams
+    /// Pop top value, print to stdout without a newline ( a --  )
+    Write,
+    /// Pop top value, print to stderr ( a --  )
+    EPrint,
+    /// read_line ( -- str/nil )
+    ReadLine,
+    /// read_all ( -- str )
+    ReadAll,
+
+    // Process
+    /// args ( -- [str] )
+    Args,
+    /// env ( name -- str/nil )
+    Env,
+    /// exit ( code -- )
+    Exit,
+    /// script_path ( -- str/nil )
+    ScriptPath,
+
+    // Random
+    /// rand ( -- f64 )
+    Rand,
+    /// rand_int ( lo hi -- i64 )
+    RandInt,
+    /// shuffle ( [a] -- [a] )
+    Shuffle,
+    /// choice ( [a] -- a/nil )
+    Choice,
+    /// seed ( i64 -- )
+    Seed,
+
+    // Time
+    /// now ( -- f64 )
+    Now,
+    /// monotonic ( -- ns )
+    Monotonic,
+    /// sleep ( secs -- )
+    Sleep,
+    /// fmt_time ( secs format -- str )
+    FmtTime,
+    /// parse_time ( str format -- secs/nil )
+    ParseTime,
+
+    // Data formats
+    /// json_parse ( str -- value )
+    JsonParse,
+    /// json_str ( value -- str )
+    JsonStr,
+    /// csv_parse ( str -- [[str]] )
+    CsvParse,
+    /// csv_parse_with ( str #{delim header} -- [[str]]/[map] )
+    CsvParseWith,
+    /// csv_str ( [[a]]/[map] -- str )
+    CsvStr,
+    /// csv_str_with ( [[a]]/[map] #{delim header} -- str )
+    CsvStrWith,
+}
+
+impl Builtin {
+    /// Builtins only emitted by `-> struct` declarations, which are not words a script can use, so
+    /// struct names and fields always match a declaration
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            Self::StructNew | Self::GetField | Self::SetField | Self::IsA
+        )
+    }
+
+    /// Builtins that touch the file system, which an embedder can disable
+    pub fn uses_files(&self) -> bool {
+        matches!(
+            self,
+            Self::ReadFile
+                | Self::WriteFile
+                | Self::AppendFile
+                | Self::ReadLines
+                | Self::Exists
+                | Self::ListDir
+                | Self::MakeDir
+                | Self::Remove
+        )
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -106,6 +314,18 @@ pub enum Intrinsic {
     Call,
     Loop,
     If,
+    Cond,
+    Case,
+    While,
+    Until,
+    DoWhile,
+    Lines,
+    Times,
+    For,
+    EachIndex,
+    Bench,
+    Break,
+    Continue,
     DefineRegister,
     StackDump,
     SymDump,
@@ -117,9 +337,44 @@ impl Intrinsic {
             "call" => Some(Self::Call),
             "loop" => Some(Self::Loop),
             "if" => Some(Self::If),
+            "cond" => Some(Self::Cond),
+            "case" => Some(Self::Case),
+            "while" => Some(Self::While),
+            "until" => Some(Self::Until),
+            "do_while" => Some(Self::DoWhile),
+            "lines" => Some(Self::Lines),
+            "times" => Some(Self::Times),
+            "for" => Some(Self::For),
+            "each_index" => Some(Self::EachIndex),
+            "bench" => Some(Self::Bench),
+            "break" => Some(Self::Break),
+            "continue" => Some(Self::Continue),
             "stack_dump" => Some(Self::StackDump),
             "sym_dump" => Some(Self::SymDump),
             _ => None,
         }
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Call => "?",
+            Self::Loop => "loop",
+            Self::If => "if",
+            Self::Cond => "cond",
+            Self::Case => "case",
+            Self::While => "while",
+            Self::Until => "until",
+            Self::DoWhile => "do_while",
+            Self::Lines => "lines",
+            Self::Times => "times",
+            Self::For => "for",
+            Self::EachIndex => "each_index",
+            Self::Bench => "bench",
+            Self::Break => "break",
+            Self::Continue => "continue",
+            Self::DefineRegister => "!",
+            Self::StackDump => "stack_dump",
+            Self::SymDump => "sym_dump",
+        }
+    }
 }
@@ -0,0 +1,104 @@
+use std::fmt::{self, Display};
+
+use crate::utils::error::{night_err, NightError, Status};
+
+/// A stack effect as written in a definition, e.g. `( x q -- x )`
+#[derive(Clone, Debug)]
+pub struct Effect {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+/// The number of values a sequence of instructions consumes and produces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity {
+    pub inputs: usize,
+    pub outputs: usize,
+}
+
+impl Effect {
+    /// Parses the inside of the parentheses. Nested groups, as in `p: ( x -- y )`, describe the
+    /// preceeding name and are skipped.
+    pub fn parse(decl: &str) -> Status<Self> {
+        let mut inputs = Vec::new();
+        let mut outputs = Vec::new();
+        let mut seen_sep = false;
+        let mut depth = 0usize;
+
+        for word in decl.split_whitespace() {
+            let opens = word.matches('(').count();
+            let closes = word.matches(')').count();
+            if depth == 0 && opens == 0 && closes == 0 {
+                if word == "--" {
+                    if seen_sep {
+                        return night_err!(
+                            Syntax,
+                            "Stack effect declaration can only contain one '--'."
+                        );
+                    }
+                    seen_sep = true;
+                } else if seen_sep {
+                    outputs.push(word.trim_end_matches(':').to_string());
+                } else {
+                    inputs.push(word.trim_end_matches(':').to_string());
+                }
+            }
+
+            depth = (depth + opens)
+                .checked_sub(closes)
+                .ok_or(NightError::Syntax(
+                    "Unbalanced parenthesis in stack effect declaration.".to_string(),
+                ))?;
+        }
+
+        if depth != 0 {
+            return night_err!(
+                Syntax,
+                "Unbalanced parenthesis in stack effect declaration."
+            );
+        }
+
+        Ok(Self { inputs, outputs })
+    }
+
+    /// Row variables like `..a` stand for any number of values, so such effects have no fixed arity
+    pub fn arity(&self) -> Option<Arity> {
+        let is_row = |w: &String| w.contains("..");
+        if self.inputs.iter().chain(&self.outputs).any(is_row) {
+            None
+        } else {
+            Some(Arity {
+                inputs: self.inputs.len(),
+                outputs: self.outputs.len(),
+            })
+        }
+    }
+}
+
+impl Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let words = self
+            .inputs
+            .iter()
+            .chain(std::iter::once(&"--".to_string()))
+            .chain(&self.outputs)
+            .cloned()
+            .collect::<Vec<_>>();
+        write!(f, "( {} )", words.join(" "))
+    }
+}
+
+// Builtin arities are `(inputs, outputs)`
+impl From<(usize, usize)> for Arity {
+    fn from((inputs, outputs): (usize, usize)) -> Self {
+        Self { inputs, outputs }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({} in, {} out)", self.inputs, self.outputs)
+    }
+}
+
+pub type CheckResult<T> = Result<T, (NightError, usize)>;
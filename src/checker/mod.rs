@@ -0,0 +1,80 @@
+mod effect;
+mod types;
+
+use std::collections::HashMap;
+
+pub use effect::{Arity, CheckResult, Effect};
+pub use types::{Sig, Ty};
+
+use crate::builtin::Builtin;
+use crate::interpreter::Instr;
+use crate::utils::error::NightError;
+
+/// Static checks run over newly parsed code before it is executed
+#[derive(Clone, Default)]
+pub struct Checker {
+    declared: HashMap<String, Effect>,
+    sigs: HashMap<String, Sig>,
+    // Type errors are only reported when asked for, e.g. by `night check`
+    strict_types: bool,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn declare(&mut self, name: String, effect: Effect) {
+        self.declared.insert(name, effect);
+    }
+
+    pub fn set_strict_types(&mut self, strict: bool) {
+        self.strict_types = strict;
+    }
+
+    /// Verifies every definition in `instrs` against its declared stack effect, and records the
+    /// inferred effects of undeclared definitions so later code can be checked against them. With
+    /// strict types, the types flowing through `instrs` are checked as well.
+    pub fn check(&mut self, instrs: &[Instr]) -> CheckResult<()> {
+        for (i, instr) in instrs.iter().enumerate() {
+            if !matches!(instr, Instr::Internal(Builtin::Def, _)) || i < 2 {
+                continue;
+            }
+            let (name, name_span) = match &instrs[i - 1] {
+                Instr::Push(v, s) if v.is_str() => (v.clone().as_str().unwrap(), *s),
+                _ => continue,
+            };
+
+            // A single word body, like `-> h ( a -- b ) g`, is just that word
+            let body = match &instrs[i - 2] {
+                Instr::PushFunc(f, s) => f.gen_instrs(*s),
+                instr => vec![instr.clone()],
+            };
+
+            // Declared first, so recursive definitions use the declaration
+            let declared = self.declared.get(&name).cloned();
+            if let Some(arity) = declared.as_ref().and_then(Effect::arity) {
+                self.sigs
+                    .insert(name.clone(), Sig::untyped(arity.inputs, arity.outputs));
+            }
+
+            let Some(sig) = types::infer(&body, &self.sigs, self.strict_types)? else {
+                continue;
+            };
+            if let Some(effect) = declared.filter(|e| e.arity().is_some_and(|a| a != sig.arity())) {
+                let msg = format!(
+                    "'{name}' is declared as {effect}, but its body has the effect {}.",
+                    sig.arity()
+                );
+                return Err((NightError::StackEffect(msg), name_span));
+            }
+            self.sigs.insert(name, sig);
+        }
+
+        if self.strict_types {
+            types::infer(instrs, &self.sigs, true)?;
+        }
+
+        Ok(())
+    }
+}
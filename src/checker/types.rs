@@ -0,0 +1,681 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use super::{Arity, CheckResult};
+use crate::builtin::{Builtin, Intrinsic as Intr, Operator};
+use crate::interpreter::Instr;
+use crate::utils::error::NightError;
+use crate::value::Value;
+
+/// The static type of a stack slot, `Any` when it cannot be known
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ty {
+    Int,
+    Float,
+    Str,
+    Bool,
+    Array,
+    Map,
+    Struct,
+    Nil,
+    Function,
+    Any,
+}
+
+/// What an instruction requires of one of its arguments
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Want {
+    // Strictly an integer, as the bitwise builtins refuse to truncate floats
+    Int,
+    Num,
+    Str,
+    Array,
+    Map,
+    Struct,
+    // Anything with a length
+    Sized,
+    Function,
+    // Anything but a function
+    Value,
+    Any,
+}
+
+/// The type of a value an instruction produces
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Out {
+    Is(Ty),
+    // The same type as the input at this index, counted from the deepest input
+    Same(usize),
+    // The result of `+`, `-`, etc. on the two inputs
+    Arith,
+}
+
+/// The typed stack effect of a builtin or definition, inputs are listed deepest first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sig {
+    inputs: Vec<Want>,
+    outputs: Vec<Out>,
+}
+
+impl Ty {
+    pub fn of(value: &Value) -> Self {
+        if value.is_int() {
+            Ty::Int
+        } else if value.is_float() {
+            Ty::Float
+        } else if value.is_str() {
+            Ty::Str
+        } else if value.is_bool() {
+            Ty::Bool
+        } else if value.is_array() {
+            Ty::Array
+        } else if value.is_map() {
+            Ty::Map
+        } else if value.is_struct() {
+            Ty::Struct
+        } else {
+            Ty::Nil
+        }
+    }
+
+    fn join(self, other: Ty) -> Ty {
+        if self == other {
+            self
+        } else {
+            Ty::Any
+        }
+    }
+
+    fn is_num(self) -> bool {
+        matches!(self, Ty::Int | Ty::Float)
+    }
+}
+
+impl Want {
+    fn accepts(self, ty: Ty) -> bool {
+        match (self, ty) {
+            (Want::Any, _) | (_, Ty::Any) => true,
+            (Want::Int, t) => t == Ty::Int,
+            (Want::Num, t) => t.is_num(),
+            (Want::Str, t) => t == Ty::Str,
+            (Want::Array, t) => t == Ty::Array,
+            (Want::Map, t) => t == Ty::Map,
+            (Want::Struct, t) => t == Ty::Struct,
+            (Want::Sized, t) => matches!(t, Ty::Str | Ty::Array | Ty::Map),
+            (Want::Function, t) => t == Ty::Function,
+            (Want::Value, t) => t != Ty::Function,
+        }
+    }
+}
+
+impl From<Ty> for Out {
+    fn from(ty: Ty) -> Self {
+        Out::Is(ty)
+    }
+}
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Display for Want {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Want::Int => "integer",
+            Want::Num => "number",
+            Want::Str => "string",
+            Want::Array => "array",
+            Want::Map => "map",
+            Want::Struct => "struct",
+            Want::Sized => "string, array or map",
+            Want::Function => "function",
+            Want::Value => "value",
+            Want::Any => "anything",
+        };
+        write!(f, "{name}")
+    }
+}
+
+macro_rules! sig {
+    ($($i:ident),* => $($o:expr),*) => {
+        Some(Sig {
+            inputs: vec![$(Want::$i),*],
+            outputs: vec![$(Out::from($o)),*],
+        })
+    };
+}
+
+impl Sig {
+    fn op(op: Operator) -> Option<Sig> {
+        use Operator::*;
+
+        match op {
+            Add => sig!(Value, Value => Out::Arith),
+            Sub | Mul | Div | Mod => sig!(Num, Num => Out::Arith),
+            Eq | NotEq => sig!(Any, Any => Ty::Bool),
+            Greater | Less | GreaterEq | LessEq => sig!(Value, Value => Ty::Bool),
+            Not => sig!(Value => Ty::Bool),
+            BitAnd | BitOr | BitXor | ShiftLeft | ShiftRight => sig!(Int, Int => Ty::Int),
+            Pop => sig!(Any =>),
+            Swap => sig!(Any, Any => Out::Same(1), Out::Same(0)),
+            Dup => sig!(Any => Out::Same(0), Out::Same(0)),
+            Call => None,
+        }
+    }
+
+    fn builtin(b: Builtin) -> Option<Sig> {
+        use Builtin::*;
+        use Out::Same;
+
+        match b {
+            Print => sig!(Any =>),
+            Inc | Dec | Floor | Ceil => sig!(Num => Same(0)),
+            Def => sig!(Any, Str =>),
+            Undef => sig!(Str => Ty::Any),
+            UndefReg => sig!(Str =>),
+            Over => sig!(Any, Any => Same(0), Same(1), Same(0)),
+            Dupd => sig!(Any, Any => Same(0), Same(0), Same(1)),
+            Swapd => sig!(Any, Any, Any => Same(1), Same(0), Same(2)),
+            Pop2 => sig!(Any, Any =>),
+            Pop3 => sig!(Any, Any, Any =>),
+            Popd => sig!(Any, Any => Same(1)),
+            Dup2 => sig!(Any, Any => Same(0), Same(1), Same(0), Same(1)),
+            Dup3 => sig!(Any, Any, Any => Same(0), Same(1), Same(2), Same(0), Same(1), Same(2)),
+            Pick => sig!(Any, Any, Any => Same(0), Same(1), Same(2), Same(0)),
+            LogicalAnd | LogicalOr => sig!(Value, Value => Ty::Bool),
+            CastToInt => sig!(Num => Ty::Int),
+            CastToFloat => sig!(Num => Ty::Float),
+            BitNot | PopCount => sig!(Int => Ty::Int),
+            TestBit => sig!(Int, Int => Ty::Bool),
+            Len => sig!(Sized => Ty::Int),
+            Slice => sig!(Str, Num, Num => Ty::Str),
+            IndexOf => sig!(Str, Str => Ty::Any),
+            Split => sig!(Str, Str => Ty::Array),
+            Join => sig!(Array, Str => Ty::Str),
+            Upper | Lower | Trim => sig!(Str => Ty::Str),
+            Replace => sig!(Str, Str, Str => Ty::Str),
+            StartsWith | EndsWith => sig!(Str, Str => Ty::Bool),
+            Chars => sig!(Str => Ty::Array),
+            Ord => sig!(Str => Ty::Int),
+            Chr => sig!(Num => Ty::Str),
+            Repeat => sig!(Str, Num => Ty::Str),
+            Get => sig!(Map, Value => Ty::Any),
+            Set => sig!(Map, Value, Value => Ty::Map),
+            Has => sig!(Map, Value => Ty::Bool),
+            Del => sig!(Map, Value => Ty::Map),
+            Keys | Values | Entries => sig!(Map => Ty::Array),
+            Default => sig!(Value, Value => Ty::Any),
+            IsSome => sig!(Value => Ty::Bool),
+            Parse => sig!(Str => Ty::Any),
+            Curry => sig!(Any, Function => Ty::Function),
+            Bind | Prepose => sig!(Function, Function => Ty::Function),
+            QuotLen => sig!(Function => Ty::Int),
+            QuotNth => sig!(Function, Num => Ty::Any),
+            QuotAppend => sig!(Function, Any => Ty::Function),
+            ToQuot => sig!(Array => Ty::Function),
+            FromQuot => sig!(Function => Ty::Array),
+            GetField => sig!(Struct, Str, Str => Ty::Any),
+            SetField => sig!(Struct, Value, Str, Str => Same(0)),
+            IsA => sig!(Value, Str => Ty::Bool),
+            Range => sig!(Int, Int => Ty::Array),
+            RangeBy => sig!(Int, Int, Int => Ty::Array),
+            ReadFile => sig!(Str => Ty::Str),
+            WriteFile | AppendFile => sig!(Str, Str =>),
+            ReadLines | ListDir => sig!(Str => Ty::Array),
+            Exists => sig!(Str => Ty::Bool),
+            MakeDir | Remove => sig!(Str =>),
+            Write | EPrint => sig!(Any =>),
+            ReadLine => sig!(=> Ty::Any),
+            ReadAll => sig!(=> Ty::Str),
+            Args => sig!(=> Ty::Array),
+            Env => sig!(Str => Ty::Any),
+            Exit => sig!(Int =>),
+            ScriptPath => sig!(=> Ty::Any),
+            Rand => sig!(=> Ty::Float),
+            RandInt => sig!(Int, Int => Ty::Int),
+            Shuffle => sig!(Array => Ty::Array),
+            Choice => sig!(Array => Ty::Any),
+            Seed => sig!(Int =>),
+            Now => sig!(=> Ty::Float),
+            Monotonic => sig!(=> Ty::Int),
+            Sleep => sig!(Num =>),
+            FmtTime => sig!(Num, Str => Ty::Str),
+            ParseTime => sig!(Str, Str => Ty::Any),
+            JsonParse => sig!(Str => Ty::Any),
+            JsonStr => sig!(Value => Ty::Str),
+            CsvParse => sig!(Str => Ty::Array),
+            CsvParseWith => sig!(Str, Map => Ty::Array),
+            CsvStr => sig!(Array => Ty::Str),
+            CsvStrWith => sig!(Array, Map => Ty::Str),
+            Rot | RotRight | Format | Compose | Unquote | StructNew => None,
+        }
+    }
+
+    pub fn arity(&self) -> Arity {
+        Arity {
+            inputs: self.inputs.len(),
+            outputs: self.outputs.len(),
+        }
+    }
+
+    /// A signature that only knows the arity, used for declared and recursive definitions
+    pub fn untyped(inputs: usize, outputs: usize) -> Sig {
+        Sig {
+            inputs: vec![Want::Any; inputs],
+            outputs: vec![Out::Is(Ty::Any); outputs],
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Slot {
+    ty: Ty,
+    // Which input of the code being checked this value is, counted in the order they were popped
+    origin: Option<usize>,
+    // The signature of a function literal, if it could be inferred
+    quot: Option<Rc<Sig>>,
+}
+
+impl Slot {
+    fn of(ty: Ty) -> Self {
+        Self {
+            ty,
+            origin: None,
+            quot: None,
+        }
+    }
+}
+
+/// Abstract interpretation of a sequence of instructions that tracks the type of every slot. When
+/// the effect of an instruction cannot be known (calling a function passed as an input, a variable
+/// arity builtin, an unknown symbol), everything below it is forgotten and checking continues, but
+/// no signature is inferred.
+#[derive(Clone)]
+struct Machine<'a> {
+    sigs: &'a HashMap<String, Sig>,
+    // Whether type errors are reported, otherwise only the arity of the code matters
+    strict: bool,
+    stack: Vec<Slot>,
+    // What the code requires of each of its inputs, in the order they were popped
+    wants: Vec<Want>,
+    regs: HashMap<String, Ty>,
+    marks: Vec<usize>,
+    opaque: bool,
+}
+
+impl<'a> Machine<'a> {
+    fn new(sigs: &'a HashMap<String, Sig>, strict: bool) -> Self {
+        Self {
+            sigs,
+            strict,
+            stack: Vec::new(),
+            wants: Vec::new(),
+            regs: HashMap::new(),
+            marks: Vec::new(),
+            opaque: false,
+        }
+    }
+
+    fn pop(&mut self) -> Slot {
+        if let Some(slot) = self.stack.pop() {
+            return slot;
+        }
+        if self.opaque {
+            return Slot::of(Ty::Any);
+        }
+
+        self.wants.push(Want::Any);
+        Slot {
+            ty: Ty::Any,
+            origin: Some(self.wants.len() - 1),
+            quot: None,
+        }
+    }
+
+    fn forget(&mut self) {
+        self.stack.clear();
+        self.marks.clear();
+        self.opaque = true;
+    }
+
+    fn mismatch(&self, msg: String, span: usize) -> CheckResult<()> {
+        if self.strict {
+            return Err((NightError::UnsupportedType(msg), span));
+        }
+        Ok(())
+    }
+
+    // `depth` is the position of the slot counted from the top of the stack, starting at 1
+    fn require(&mut self, slot: &Slot, want: Want, depth: usize, span: usize) -> CheckResult<()> {
+        if !want.accepts(slot.ty) {
+            return self.mismatch(
+                format!("Expected {want}, got {} at slot {depth}.", slot.ty),
+                span,
+            );
+        }
+
+        if let Some(i) = slot.origin.filter(|_| slot.ty == Ty::Any) {
+            // Keep the most specific requirement, anything is narrower than `Value`
+            if matches!(self.wants[i], Want::Any | Want::Value) && want != Want::Any {
+                self.wants[i] = want;
+            }
+        }
+        Ok(())
+    }
+
+    // Pops the inputs of `sig`, checking each of them, and returns them deepest first
+    fn take(&mut self, sig: &Sig, span: usize) -> CheckResult<Vec<Slot>> {
+        let mut args = Vec::with_capacity(sig.inputs.len());
+        for (depth, want) in sig.inputs.iter().rev().enumerate() {
+            let slot = self.pop();
+            self.require(&slot, *want, depth + 1, span)?;
+            args.push(slot);
+        }
+        args.reverse();
+        Ok(args)
+    }
+
+    fn output(&mut self, out: Out, args: &[Slot], span: usize) -> CheckResult<Slot> {
+        Ok(match out {
+            Out::Is(ty) => Slot::of(ty),
+            Out::Same(i) => args[i].clone(),
+            Out::Arith => {
+                let (l, r) = (args[0].ty, args[1].ty);
+                // Numbers can only be combined with numbers, and strings with strings
+                let want = |t: Ty| if t == Ty::Str { Want::Str } else { Want::Num };
+                match (l, r) {
+                    (Ty::Any, Ty::Any) => {}
+                    (Ty::Any, r) => self.require(&args[0], want(r), 2, span)?,
+                    (l, Ty::Any) => self.require(&args[1], want(l), 1, span)?,
+                    _ => {}
+                }
+
+                // e.g. `. +` has the type of its input
+                if args[0].origin.is_some() && args[0].origin == args[1].origin {
+                    return Ok(Slot {
+                        quot: None,
+                        ..args[0].clone()
+                    });
+                }
+
+                let ty = match (l, r) {
+                    (Ty::Any, _) | (_, Ty::Any) => Ty::Any,
+                    (Ty::Int, Ty::Int) => Ty::Int,
+                    (Ty::Str, Ty::Str) => Ty::Str,
+                    (l, r) if l.is_num() && r.is_num() => Ty::Float,
+                    (l, r) => {
+                        self.mismatch(format!("Cannot add {l} and {r}."), span)?;
+                        Ty::Any
+                    }
+                };
+                Slot::of(ty)
+            }
+        })
+    }
+
+    fn apply(&mut self, sig: &Sig, span: usize) -> CheckResult<()> {
+        let args = self.take(sig, span)?;
+        for out in &sig.outputs {
+            let slot = self.output(*out, &args, span)?;
+            self.stack.push(slot);
+        }
+        Ok(())
+    }
+
+    // Whether any of the top `n` slots holds a function
+    fn passes_function(&self, n: usize) -> bool {
+        let args = &self.stack[self.stack.len().saturating_sub(n)..];
+        args.iter().any(|slot| slot.ty == Ty::Function)
+    }
+
+    fn run(mut self, instrs: &[Instr]) -> CheckResult<Option<Sig>> {
+        let mut iter = instrs.iter();
+        while let Some(instr) = iter.next() {
+            let span = instr.get_span();
+            match instr {
+                Instr::Push(v, _) => self.stack.push(Slot::of(Ty::of(v))),
+                Instr::PushSym(reg, true, _) => {
+                    let ty = self.regs.get(reg).copied().unwrap_or(Ty::Any);
+                    self.stack.push(Slot::of(ty));
+                }
+                Instr::PushFunc(f, s) => {
+                    let quot = Machine::new(self.sigs, self.strict).run(&f.gen_instrs(*s))?;
+                    self.stack.push(Slot {
+                        ty: Ty::Function,
+                        origin: None,
+                        quot: quot.map(Rc::new),
+                    });
+                }
+                // A declared effect like `( x block -- x )` assumes the block leaves the stack as
+                // it found it, so it says nothing when a function literal is passed in
+                Instr::PushSym(name, false, _) => match self.sigs.get(name) {
+                    Some(sig) if !self.passes_function(sig.inputs.len()) => {
+                        self.apply(sig, span)?
+                    }
+                    _ => self.forget(),
+                },
+                Instr::Op(o, _) => match Sig::op(*o) {
+                    Some(sig) => self.apply(&sig, span)?,
+                    None => self.forget(),
+                },
+                Instr::Internal(b, _) => match Sig::builtin(*b) {
+                    Some(sig) => self.apply(&sig, span)?,
+                    None => self.forget(),
+                },
+                Instr::Intrinsic(Intr::Call, _) => {
+                    let f = self.pop();
+                    self.require(&f, Want::Function, 1, span)?;
+                    match f.quot {
+                        Some(sig) => self.apply(&sig, span)?,
+                        None => self.forget(),
+                    }
+                }
+                Instr::Intrinsic(Intr::If, _) => {
+                    let false_def = self.pop();
+                    self.require(&false_def, Want::Function, 1, span)?;
+                    let true_def = self.pop();
+                    self.require(&true_def, Want::Function, 2, span)?;
+                    let cond = self.pop();
+                    self.require(&cond, Want::Value, 3, span)?;
+
+                    match (true_def.quot, false_def.quot) {
+                        (Some(t), Some(f))
+                            if t.inputs.len() == f.inputs.len()
+                                && t.outputs.len() == f.outputs.len() =>
+                        {
+                            let mut other = self.clone();
+                            self.apply(&t, span)?;
+                            other.apply(&f, span)?;
+
+                            // Both branches leave the stack at the same depth, so merge them
+                            for (slot, o) in self.stack.iter_mut().zip(other.stack) {
+                                if slot.ty != o.ty || slot.origin != o.origin {
+                                    *slot = Slot::of(slot.ty.join(o.ty));
+                                }
+                            }
+                            for (want, o) in self.wants.iter_mut().zip(other.wants) {
+                                if *want == Want::Any {
+                                    *want = o;
+                                }
+                            }
+                            for (reg, ty) in self.regs.iter_mut() {
+                                *ty = ty.join(other.regs.get(reg).copied().unwrap_or(Ty::Any));
+                            }
+                        }
+                        _ => self.forget(),
+                    }
+                }
+                Instr::Intrinsic(Intr::Loop, _) => {
+                    let body = self.pop();
+                    self.require(&body, Want::Function, 1, span)?;
+                    let count = self.pop();
+                    self.require(&count, Want::Num, 2, span)?;
+
+                    // The body may run any number of times, so only its inputs can be checked
+                    match body.quot {
+                        Some(sig) if sig.inputs.len() == sig.outputs.len() => {
+                            self.take(&sig, span)?;
+                            for _ in 0..sig.outputs.len() {
+                                self.stack.push(Slot::of(Ty::Any));
+                            }
+                        }
+                        _ => self.forget(),
+                    }
+                }
+                Instr::Intrinsic(Intr::Cond | Intr::Case, _) => {
+                    let clauses = self.pop();
+                    self.require(&clauses, Want::Function, 1, span)?;
+                    self.forget();
+                }
+                Instr::Intrinsic(Intr::While | Intr::Until | Intr::DoWhile, _) => {
+                    let second = self.pop();
+                    self.require(&second, Want::Function, 1, span)?;
+                    let first = self.pop();
+                    self.require(&first, Want::Function, 2, span)?;
+                    self.forget();
+                }
+                // Same as `loop`, except the body is also given the index, item and/or line
+                Instr::Intrinsic(Intr::Times | Intr::For | Intr::EachIndex | Intr::Lines, _) => {
+                    let body = self.pop();
+                    self.require(&body, Want::Function, 1, span)?;
+                    let want = match instr {
+                        Instr::Intrinsic(Intr::Times, _) => Some(Want::Num),
+                        Instr::Intrinsic(Intr::Lines, _) => None,
+                        _ => Some(Want::Sized),
+                    };
+                    if let Some(want) = want {
+                        let seq = self.pop();
+                        self.require(&seq, want, 2, span)?;
+                    }
+                    let given = if matches!(instr, Instr::Intrinsic(Intr::EachIndex, _)) {
+                        2
+                    } else {
+                        1
+                    };
+
+                    match body.quot {
+                        Some(sig) if sig.inputs.len() == sig.outputs.len() + given => {
+                            for _ in 0..sig.outputs.len() {
+                                self.pop();
+                            }
+                            for _ in 0..sig.outputs.len() {
+                                self.stack.push(Slot::of(Ty::Any));
+                            }
+                        }
+                        _ => self.forget(),
+                    }
+                }
+                // Same as `call`, leaving the duration on top
+                Instr::Intrinsic(Intr::Bench, _) => {
+                    let body = self.pop();
+                    self.require(&body, Want::Function, 1, span)?;
+                    match body.quot {
+                        Some(sig) => {
+                            self.apply(&sig, span)?;
+                            self.stack.push(Slot::of(Ty::Int));
+                        }
+                        None => self.forget(),
+                    }
+                }
+                Instr::Intrinsic(Intr::Break | Intr::Continue, _) => self.forget(),
+                Instr::Intrinsic(Intr::DefineRegister, _) => {
+                    let value = self.pop();
+                    // The following instruction is the register being defined
+                    if let Some(Instr::PushSym(reg, true, _)) = iter.next() {
+                        self.regs.insert(reg.clone(), value.ty);
+                    }
+                }
+                Instr::Intrinsic(Intr::StackDump | Intr::SymDump, _)
+                | Instr::Guard(..)
+                | Instr::GuardEnd(..)
+                | Instr::Block(..)
+                | Instr::Unblock(..)
+                | Instr::EndCallback
+                | Instr::Resume(_)
+                | Instr::Unbind(..) => {}
+                Instr::ArrayStart(_) | Instr::MapStart(_) => self.marks.push(self.stack.len()),
+                Instr::ArrayEnd(_) | Instr::MapEnd(_) => match self.marks.pop() {
+                    // Same as `take_collection`, which fails at runtime
+                    Some(mark) if mark > self.stack.len() => {
+                        let kind = if matches!(instr, Instr::ArrayEnd(_)) {
+                            "array"
+                        } else {
+                            "map"
+                        };
+                        return Err((
+                            NightError::StackEffect(format!(
+                                "{kind} body consumes values from outside of the {kind}."
+                            )),
+                            span,
+                        ));
+                    }
+                    Some(mark) => {
+                        self.stack.truncate(mark);
+                        let ty = if matches!(instr, Instr::ArrayEnd(_)) {
+                            Ty::Array
+                        } else {
+                            Ty::Map
+                        };
+                        self.stack.push(Slot::of(ty));
+                    }
+                    None => {
+                        self.forget();
+                        self.stack.push(Slot::of(Ty::Any));
+                    }
+                },
+            }
+        }
+
+        if self.opaque {
+            return Ok(None);
+        }
+
+        // Inputs were numbered as they were popped, so the deepest one has the highest number
+        let count = self.wants.len();
+        let outputs = self
+            .stack
+            .iter()
+            .map(|slot| match slot.origin {
+                Some(i) if slot.ty == Ty::Any => Out::Same(count - 1 - i),
+                _ => Out::Is(slot.ty),
+            })
+            .collect();
+        self.wants.reverse();
+        Ok(Some(Sig {
+            inputs: self.wants,
+            outputs,
+        }))
+    }
+}
+
+/// Infers the typed stack effect of `instrs`, using `sigs` for user defined symbols. Collections
+/// consuming values from outside of themselves are always reported, type errors only if `strict`.
+pub fn infer(
+    instrs: &[Instr],
+    sigs: &HashMap<String, Sig>,
+    strict: bool,
+) -> CheckResult<Option<Sig>> {
+    Machine::new(sigs, strict).run(instrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{BUILTIN_MAP, OP_MAP};
+
+    #[test]
+    fn sigs_match_registered_arities() {
+        let arity = |sig: Option<Sig>| sig.map(|s| (s.inputs.len(), s.outputs.len()));
+        for (name, op) in OP_MAP.entries() {
+            if let Some(sig) = arity(Sig::op(*op)) {
+                assert_eq!(Some(sig), op.arity(), "{name}");
+            }
+        }
+        for (name, b) in BUILTIN_MAP.entries() {
+            assert_eq!(arity(Sig::builtin(*b)), b.arity(), "{name}");
+        }
+    }
+}
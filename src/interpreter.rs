@@ -4,12 +4,22 @@ use std::fmt::{self, Debug, Display};
 use std::rc::Rc;
 use std::vec::IntoIter;
 
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
 use crate::builtin::{Builtin, Intrinsic as Intr, Operator, BUILTIN_MAP};
+use crate::checker::{Checker, Effect};
 use crate::lexer::{LexTok, Token};
 use crate::scope::{Scope, ScopeInternal, StackVal};
+use crate::utils;
 use crate::utils::error::{self, night_err, NightError, Span, Status};
-use crate::utils::function::{BlockFunc, Generable, SingleFunc};
-use crate::value::Value;
+use crate::utils::format;
+use crate::utils::function::{self, BlockFunc, Generable, SingleFunc};
+use crate::utils::output::{Reader, Writer};
+use crate::utils::pattern;
+use crate::utils::process::Process;
+use crate::utils::time;
+use crate::value::{self, Value};
 
 #[derive(Clone)]
 pub enum Instr {
@@ -23,7 +33,15 @@ pub enum Instr {
     GuardEnd(Vec<String>, usize),
     Block(Vec<String>, usize),
     Unblock(Vec<String>, usize),
+    ArrayStart(usize),
+    ArrayEnd(usize),
+    MapStart(usize),
+    MapEnd(usize),
     EndCallback,
+    // Marks the end of a loop body, see `Frame`
+    Resume(usize),
+    // Restores the registers bound by a `case` clause to the values they had before it
+    Unbind(Vec<(String, Option<StackVal>)>, usize),
 }
 
 impl Instr {
@@ -39,11 +57,73 @@ impl Instr {
             Instr::GuardEnd(_, s) => *s,
             Instr::Block(_, s) => *s,
             Instr::Unblock(_, s) => *s,
+            Instr::ArrayStart(s) => *s,
+            Instr::ArrayEnd(s) => *s,
+            Instr::MapStart(s) => *s,
+            Instr::MapEnd(s) => *s,
             Instr::EndCallback => usize::MAX,
+            Instr::Resume(s) => *s,
+            Instr::Unbind(_, s) => *s,
         }
     }
 }
 
+/// A running loop. Only one copy of the body is queued at a time, followed by an `Instr::Resume`
+/// that decides what runs next, so memory stays constant regardless of the number of iterations.
+enum Frame {
+    While {
+        cond: Rc<dyn Generable>,
+        body: Rc<dyn Generable>,
+        until: bool,
+        // Whether the condition's result is on the stack, waiting to be tested
+        testing: bool,
+    },
+    Count {
+        body: Rc<dyn Generable>,
+        index: i64,
+        count: i64,
+        push_index: bool,
+    },
+    Each {
+        body: Rc<dyn Generable>,
+        items: std::vec::IntoIter<Value>,
+        index: i64,
+        push_index: bool,
+    },
+    // Reads the input one line per iteration, until its end
+    Lines {
+        body: Rc<dyn Generable>,
+    },
+}
+
+impl Frame {
+    /// Arrays are iterated by element, strings by character and maps by `[key value]` entry
+    fn each(body: Rc<dyn Generable>, seq: Value, push_index: bool) -> Status<Self> {
+        let items = if seq.is_array() {
+            seq.as_array()?
+        } else if seq.is_map() {
+            seq.as_map()?
+                .into_iter()
+                .map(|(k, v)| Value::from(vec![k, v]))
+                .collect()
+        } else if seq.is_str() {
+            seq.as_str()?.chars().map(|c| Value::from(c.to_string())).collect()
+        } else {
+            return night_err!(
+                UnsupportedType,
+                format!("Cannot iterate over '{seq}', expected an array, map or string.")
+            );
+        };
+
+        Ok(Self::Each {
+            body,
+            items: items.into_iter(),
+            index: 0,
+            push_index,
+        })
+    }
+}
+
 pub struct Night {
     input: Box<str>,
     tokens: IntoIter<LexTok>,
@@ -53,6 +133,10 @@ pub struct Night {
     instrs: VecDeque<Instr>,
     scope: Scope,
     callback: Vec<usize>,
+    frames: Vec<Frame>,
+    checker: Checker,
+    // Whether file system builtins may be used
+    file_access: bool,
 }
 
 macro_rules! push_instr {
@@ -69,6 +153,12 @@ macro_rules! push_instr {
     };
 }
 
+impl Default for Night {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Night {
     pub fn new() -> Self {
         Self {
@@ -78,6 +168,9 @@ impl Night {
             instrs: VecDeque::new(),
             scope: Rc::new(RefCell::new(ScopeInternal::create())),
             callback: vec![],
+            frames: vec![],
+            checker: Checker::new(),
+            file_access: true,
         }
     }
 
@@ -89,25 +182,69 @@ impl Night {
             instrs: instrs.into(),
             scope: Rc::new(RefCell::new(self.scope.borrow().to_owned().clone())),
             callback: vec![],
+            frames: vec![],
+            checker: self.checker.clone(),
+            file_access: self.file_access,
         }
     }
 
-    pub fn push_new_code(&mut self, code: &str, tokens: Vec<LexTok>) {
+    pub fn push_new_code(&mut self, code: &str, tokens: Vec<LexTok>) -> Status {
         self.input = code.into();
         self.tokens = tokens.into_iter();
-        self.init();
+        self.init()
     }
 
     pub fn get_scope(&self) -> Scope {
         self.scope.clone()
     }
 
-    pub fn inject_code(&mut self, tokens: Vec<LexTok>) {
+    /// Report type errors found by the checker before running, instead of only at runtime
+    pub fn set_strict_types(&mut self, strict: bool) {
+        self.checker.set_strict_types(strict);
+    }
+
+    /// Send program output (`print`, `write`, the dumps), errors and warnings to `out`, and `eprint`
+    /// to `err`. These are stdout and stderr by default.
+    pub fn set_output(&mut self, out: Writer, err: Writer) {
+        self.scope.borrow_mut().set_output(out, err);
+    }
+
+    /// Feed `read_line`, `read_all` and `lines` from `input` instead of stdin
+    pub fn set_input(&mut self, input: Reader) {
+        self.scope.borrow_mut().set_input(input);
+    }
+
+    /// Where `read_line` and friends read from
+    pub fn input(&self) -> Reader {
+        self.scope.borrow().input()
+    }
+
+    /// Where output and errors go, also used by `Lexer::with_errors`
+    pub fn out(&self) -> Writer {
+        self.scope.borrow().out()
+    }
+
+    /// Set what `args`, `env` and `script_path` return, see `Process::from_env`
+    pub fn set_process(&mut self, process: Process) {
+        self.scope.borrow_mut().set_process(process);
+    }
+
+    /// Make `rand` and friends reproducible, same as `seed`
+    pub fn set_seed(&mut self, seed: u64) {
+        *self.scope.borrow().rng().borrow_mut() = StdRng::seed_from_u64(seed);
+    }
+
+    /// Allow or deny builtins like `read_file` and `remove`, allowed by default
+    pub fn set_file_access(&mut self, allowed: bool) {
+        self.file_access = allowed;
+    }
+
+    pub fn inject_code(&mut self, tokens: Vec<LexTok>) -> Status {
         let mut tokens = tokens.into_iter();
         std::mem::swap(&mut self.tokens, &mut tokens);
-        self.init();
-        self.exec();
+        let status = self.init().and_then(|_| self.exec());
         self.tokens = tokens;
+        status
     }
 
     #[inline]
@@ -116,11 +253,35 @@ impl Night {
         self.spans.push(span);
     }
 
-    pub fn init(&mut self) {
+    pub fn init(&mut self) -> Status {
         while let Some((tok, span)) = self.tokens.next() {
             self.spans.push(span);
             if let Err(e) = self.build_instr(tok) {
-                error::error(e, self.spans[self.spans.len() - 1].clone());
+                return self.fail(e, self.spans.len() - 1, vec![]);
+            }
+        }
+
+        if let Err((e, span)) = self.checker.check(self.instrs.make_contiguous()) {
+            return self.fail(e, span, vec![]);
+        }
+        Ok(())
+    }
+
+    // Reports `e`, unless a nested run already did, and drops everything left to run. The caller
+    // still gets the error, or `NightError::Exit` from `exit`, so an embedder can decide what to do
+    // instead of being killed.
+    fn fail(&mut self, e: NightError, span: usize, trace: Vec<Span>) -> Status {
+        self.tokens = vec![].into_iter();
+        self.instrs.clear();
+        self.callback.clear();
+        self.frames.clear();
+
+        match e {
+            NightError::Reported(e) => Err(*e),
+            NightError::Exit(_) => Err(e),
+            e => {
+                error::error_with_trace(&self.out(), &e, self.spans[span].clone(), trace);
+                Err(e)
             }
         }
     }
@@ -128,14 +289,18 @@ impl Night {
     #[inline]
     fn build_instr(&mut self, tok: Token) -> Status {
         match tok {
-            Token::Number(n) => {
-                if n.contains('.') {
-                    push_instr!(Instr::Push, Value::from(n.parse::<f64>()?), self)
-                } else {
-                    push_instr!(Instr::Push, Value::from(n.parse::<i64>()?), self)
+            Token::Number(n) => push_instr!(Instr::Push, value::parse_number(&n)?, self),
+            Token::String(s) => push_instr!(Instr::Push, Value::from(s.to_string()), self),
+            Token::Bool(b) => push_instr!(Instr::Push, Value::from(b), self),
+            Token::Nil => push_instr!(Instr::Push, Value::nil(), self),
+            Token::Template(s) => {
+                let (template, regs) = format::interpolate(&s)?;
+                for reg in regs {
+                    push_instr!(Instr::PushSym, reg, true, self);
                 }
+                push_instr!(Instr::Push, Value::from(template), self);
+                push_instr!(Instr::Internal, Builtin::Format, self);
             }
-            Token::String(s) => push_instr!(Instr::Push, Value::from(s.to_string()), self),
             Token::Register(s) => push_instr!(Instr::PushSym, s.to_string(), true, self),
             Token::Op(Operator::Call) => push_instr!(Instr::Intrinsic, Intr::Call, self),
             Token::Op(o) => push_instr!(Instr::Op, o, self),
@@ -150,8 +315,17 @@ impl Night {
                 }
             }
             Token::CloseParen => return night_err!(Syntax, "Unbalanced parenthesis."),
+            Token::Effect(_) => {
+                return night_err!(
+                    Syntax,
+                    "Stack effect declarations can only follow the name of a definition."
+                )
+            }
             Token::OpenCurly => self.parse_block(None)?,
             Token::CloseCurly => return night_err!(Syntax, "Unbalanced block."),
+            Token::OpenBracket => push_instr!(Instr::ArrayStart, self),
+            Token::CloseBracket => push_instr!(Instr::ArrayEnd, self),
+            Token::OpenMap => self.parse_map()?,
             Token::DefineSym => {
                 if self.spans.len() > 1 {
                     let mut i = self.spans.len() - 2;
@@ -202,7 +376,6 @@ impl Night {
                 self.span_between(self.spans.len() - 2, self.spans.len() - 1);
                 push_instr!(Instr::PushFunc, Rc::new(SingleFunc::from(instr)), self)
             }
-            _ => return night_err!(Unimplemented, format!("Token '{tok:?}'")),
         }
 
         Ok(())
@@ -213,7 +386,7 @@ impl Night {
             let s = s.as_ref();
             if let Some(i) = Intr::from_name(s) {
                 Instr::Intrinsic(i, self.spans.len() - 1)
-            } else if let Some(&b) = BUILTIN_MAP.get(s) {
+            } else if let Some(&b) = BUILTIN_MAP.get(s).filter(|b| !b.is_internal()) {
                 Instr::Internal(b, self.spans.len() - 1)
             } else if let Some(o) = Operator::from_name(s) {
                 Instr::Op(o, self.spans.len() - 1)
@@ -266,6 +439,24 @@ impl Night {
         }
     }
 
+    // Unlike blocks, the body of a map literal is executed in place, so only the closing brace needs special handling
+    fn parse_map(&mut self) -> Status {
+        let span_start = self.spans.len() - 1;
+        push_instr!(Instr::MapStart, self);
+
+        while let Some((t, s)) = self.tokens.next() {
+            self.spans.push(s);
+            if t == Token::CloseCurly {
+                push_instr!(Instr::MapEnd, self);
+                return Ok(());
+            }
+            self.build_instr(t)?;
+        }
+
+        self.span_between(span_start, self.spans.len() - 1);
+        night_err!(Syntax, "Unbalanced map literal.")
+    }
+
     fn parse_define(&mut self) -> Status {
         let def_span = self.spans.len() - 1;
         let name;
@@ -284,6 +475,21 @@ impl Night {
             "Definition cannot be empty.".to_string(),
         ))?;
 
+        // Struct declaration, `-> struct point (x y)`
+        if let ("struct", Token::Symbol(s)) = (name.as_str(), &start) {
+            self.spans.push(span);
+            return self.parse_struct(s.to_string());
+        }
+
+        // Stack effect declaration
+        if let Token::Effect(decl) = &start {
+            self.spans.push(span);
+            self.checker.declare(name.clone(), Effect::parse(decl)?);
+            (start, span) = self.tokens.next().ok_or(NightError::Syntax(
+                "Definition cannot be empty.".to_string(),
+            ))?;
+        }
+
         // Guard expression
         let mut guard = vec![];
         if start == Token::OpenParen {
@@ -323,10 +529,106 @@ impl Night {
         Ok(())
     }
 
+    fn parse_struct(&mut self, name: String) -> Status {
+        let name_span = self.spans.len() - 1;
+        match self.tokens.next() {
+            Some((Token::OpenParen, span)) => self.spans.push(span),
+            _ => {
+                return night_err!(
+                    Syntax,
+                    format!("Expected the fields of '{name}' to follow, e.g. '(x y)'.")
+                )
+            }
+        }
+
+        let mut fields: Vec<String> = Vec::new();
+        for (tok, span) in self.tokens.by_ref() {
+            self.spans.push(span);
+            match tok {
+                Token::CloseParen => break,
+                Token::Symbol(s) if !s.contains('.') && !fields.iter().any(|f| **f == *s) => {
+                    fields.push(s.to_string())
+                }
+                Token::Symbol(s) => {
+                    return night_err!(Syntax, format!("Invalid or repeated field '{s}'."))
+                }
+                _ => return night_err!(Syntax, "Expected literal identifier."),
+            }
+        }
+        if fields.is_empty() {
+            return night_err!(
+                Syntax,
+                format!("Struct '{name}' should contain at least one field.")
+            );
+        }
+
+        match self.tokens.next() {
+            Some((Token::Newline | Token::EOF, _)) | None => {}
+            Some((_, span)) => {
+                self.spans.push(span);
+                return night_err!(Syntax, "Struct declaration must end with a newline.");
+            }
+        }
+
+        // Every generated word is a plain definition, so they are checked like any other
+        let fields_val = Value::from(
+            fields
+                .iter()
+                .map(|f| Value::from(f.as_str()))
+                .collect::<Vec<_>>(),
+        );
+        let push = |v: &str| Instr::Push(Value::from(v), name_span);
+        let mut words = vec![(
+            name.clone(),
+            format!("{} -- {name}", fields.join(" ")),
+            vec![
+                Instr::Push(fields_val, name_span),
+                push(&name),
+                Instr::Internal(Builtin::StructNew, name_span),
+            ],
+        )];
+        words.push((
+            format!("{name}?"),
+            "x -- ?".to_string(),
+            vec![push(&name), Instr::Internal(Builtin::IsA, name_span)],
+        ));
+        for field in &fields {
+            words.push((
+                format!("{name}.{field}"),
+                format!("{name} -- {field}"),
+                vec![
+                    push(&name),
+                    push(field),
+                    Instr::Internal(Builtin::GetField, name_span),
+                ],
+            ));
+            words.push((
+                format!("{name}.set_{field}"),
+                format!("{name} {field} -- {name}"),
+                vec![
+                    push(&name),
+                    push(field),
+                    Instr::Internal(Builtin::SetField, name_span),
+                ],
+            ));
+        }
+
+        for (word, effect, body) in words {
+            self.checker.declare(word.clone(), Effect::parse(&effect)?);
+            self.instrs
+                .push_back(Instr::PushFunc(Rc::new(BlockFunc::from(body)), name_span));
+            self.instrs.push_back(push(&word));
+            self.instrs
+                .push_back(Instr::Internal(Builtin::Def, name_span));
+        }
+
+        Ok(())
+    }
+
     fn parse_guard(&mut self) -> Status<Vec<String>> {
         let mut guards = Vec::new();
         let span_start = self.spans.len() - 1;
-        while let Some((tok, span)) = self.tokens.next() {
+        for (tok, span) in self.tokens.by_ref() {
             self.spans.push(span);
             match tok {
                 Token::CloseParen => break,
@@ -376,7 +678,9 @@ impl Night {
 
         if is_const {
             let mut child = self.clone_child(def);
-            child.exec();
+            child
+                .exec()
+                .map_err(|e| NightError::Reported(Box::new(e)))?;
             let mut scope = child.get_scope().borrow().to_owned();
             if scope.stack_len() != 1 {
                 return Err(NightError::NothingToPop);
@@ -420,26 +724,25 @@ impl Night {
     }
 
     #[inline]
-    pub fn exec(&mut self) {
+    pub fn exec(&mut self) -> Status {
         while let Some(instr) = self.instrs.pop_front() {
             let span = instr.get_span();
             if let Err(e) = self.exec_instr(instr) {
                 // If there is an existing trace, print that too
                 if let NightError::Warning(msg) = e {
-                    error::warn(msg, self.spans[span].clone());
-                } else if !self.callback.is_empty() {
+                    error::warn(&self.out(), msg, self.spans[span].clone());
+                } else {
                     let trace = self
                         .callback
                         .iter()
                         .rev()
                         .map(|&i| self.spans[i].clone())
                         .collect();
-                    error::error_with_trace(e, self.spans[span].clone(), trace);
-                } else {
-                    error::error(e, self.spans[span].clone());
+                    return self.fail(e, span, trace);
                 }
             }
         }
+        Ok(())
     }
 
     // Unroll the loop to avoid excessive recursion
@@ -474,6 +777,12 @@ impl Night {
             PushFunc(f, _) => self.scope.borrow_mut().push(StackVal::Function(f)),
             Intrinsic(intr, i) => self.exec_intrinsic(intr, i)?,
             Op(o, _) => o.call(self.scope.clone())?,
+            Internal(b, _) if b.uses_files() && !self.file_access => {
+                return night_err!(
+                    Runtime,
+                    format!("'{}' cannot be used, file access is disabled.", b.name())
+                );
+            }
             Internal(b, _) => b.call(self.scope.clone())?,
             Guard(guard, _) => {
                 let mut s = self.scope.borrow_mut();
@@ -503,9 +812,20 @@ impl Night {
                     s.rem_block(g);
                 }
             }
+            ArrayStart(_) | MapStart(_) => self.scope.borrow_mut().open_collection(),
+            MapEnd(_) => self.scope.borrow_mut().close_map()?,
+            ArrayEnd(_) => self.scope.borrow_mut().close_array()?,
             EndCallback => {
                 self.callback.pop();
             }
+            Resume(i) => self.resume(i)?,
+            Unbind(saved, _) => {
+                let mut s = self.scope.borrow_mut();
+                // Reversed, so a register bound twice ends up with its oldest value
+                for (reg, prev) in saved.into_iter().rev() {
+                    s.restore_reg(reg, prev);
+                }
+            }
         }
 
         Ok(())
@@ -516,16 +836,101 @@ impl Night {
         match intr {
             Intr::Call => self.exec_intr_call(from),
             Intr::If => self.exec_intr_if(from),
+            Intr::While | Intr::Until => {
+                let mut s = self.scope.borrow_mut();
+                let body = s.pop()?.as_fn()?;
+                let cond = s.pop()?.as_fn()?;
+                drop(s);
+                self.push_frame(
+                    Frame::While {
+                        cond,
+                        body,
+                        until: intr == Intr::Until,
+                        testing: false,
+                    },
+                    from,
+                );
+                self.resume(from)
+            }
+            Intr::DoWhile => {
+                let mut s = self.scope.borrow_mut();
+                let cond = s.pop()?.as_fn()?;
+                let body = s.pop()?.as_fn()?;
+                drop(s);
+                let instrs = body.gen_instrs(from);
+                self.push_frame(
+                    Frame::While {
+                        cond,
+                        body,
+                        until: false,
+                        testing: false,
+                    },
+                    from,
+                );
+                self.queue_body(instrs, from);
+                Ok(())
+            }
+            Intr::Lines => {
+                let body = self.scope.borrow_mut().pop()?.as_fn()?;
+                self.push_frame(Frame::Lines { body }, from);
+                self.resume(from)
+            }
+            Intr::Times => {
+                let mut s = self.scope.borrow_mut();
+                let body = s.pop()?.as_fn()?;
+                let count = s.pop_value()?.as_int()?;
+                drop(s);
+                if count < 0 {
+                    return night_err!(Runtime, "'times' can only take a positive integer.");
+                }
+                self.push_frame(
+                    Frame::Count {
+                        body,
+                        index: 0,
+                        count,
+                        push_index: true,
+                    },
+                    from,
+                );
+                self.resume(from)
+            }
+            Intr::For | Intr::EachIndex => {
+                let mut s = self.scope.borrow_mut();
+                let body = s.pop()?.as_fn()?;
+                let seq = s.pop_value()?;
+                drop(s);
+                let frame = Frame::each(body, seq, intr == Intr::EachIndex)?;
+                self.push_frame(frame, from);
+                self.resume(from)
+            }
+            // Runs as `{ body } monotonic start -`, so the timing covers exactly the body
+            Intr::Bench => {
+                let body = self.scope.borrow_mut().pop()?.as_fn()?;
+                let mut instrs = body.gen_instrs(from);
+                instrs.extend([
+                    Instr::Internal(Builtin::Monotonic, from),
+                    Instr::Push(Value::from(time::monotonic_ns()), from),
+                    Instr::Op(Operator::Sub, from),
+                ]);
+                self.exec_fn(instrs, from);
+                Ok(())
+            }
+            Intr::Break => self.exit_body(false),
+            Intr::Continue => self.exit_body(true),
+            Intr::Cond => self.exec_intr_cond(from),
+            Intr::Case => self.exec_intr_case(from),
             Intr::Loop => self.exec_intr_loop(from),
             Intr::DefineRegister => self.exec_intr_defr(from),
             Intr::StackDump => {
-                println!("--- STACK DMP: ---\n{}------------------", scope.borrow());
-                Ok(())
-            }
-            Intr::SymDump => {
-                scope.borrow().dump_symbols();
+                let out = scope.borrow().out();
+                writeln!(
+                    out.borrow_mut(),
+                    "--- STACK DMP: ---\n{}------------------",
+                    scope.borrow()
+                )?;
                 Ok(())
             }
+            Intr::SymDump => scope.borrow().dump_symbols(),
         }
     }
 
@@ -548,10 +953,16 @@ impl Night {
             return night_err!(Runtime, "'loop' can only take a positive integer.");
         }
 
-        for _ in 0..count {
-            self.exec_fn(def.gen_instrs(from), from);
-        }
-        Ok(())
+        self.push_frame(
+            Frame::Count {
+                body: def,
+                index: 0,
+                count,
+                push_index: false,
+            },
+            from,
+        );
+        self.resume(from)
     }
 
     fn exec_intr_if(&mut self, from: usize) -> Status {
@@ -568,6 +979,183 @@ impl Night {
         Ok(())
     }
 
+    fn push_frame(&mut self, frame: Frame, from: usize) {
+        self.callback.push(from);
+        self.frames.push(frame);
+    }
+
+    fn pop_frame(&mut self) {
+        self.callback.pop();
+        self.frames.pop();
+    }
+
+    fn queue_body(&mut self, mut instrs: Vec<Instr>, from: usize) {
+        instrs.push(Instr::Resume(from));
+        for instr in instrs.into_iter().rev() {
+            self.instrs.push_front(instr);
+        }
+    }
+
+    // Called each time the innermost loop's queued instructions have finished
+    fn resume(&mut self, from: usize) -> Status {
+        let Some(frame) = self.frames.last_mut() else {
+            return night_err!(
+                ContextFail,
+                "An internal error occurred, resumed a loop that is not running."
+            );
+        };
+
+        let next = match frame {
+            Frame::While {
+                cond,
+                body,
+                until,
+                testing,
+            } => {
+                *testing = !*testing;
+                if *testing {
+                    Some(cond.gen_instrs(from))
+                } else {
+                    let (body, until) = (body.clone(), *until);
+                    let result = self.scope.borrow_mut().pop_value()?.as_bool()?;
+                    (result != until).then(|| body.gen_instrs(from))
+                }
+            }
+            Frame::Count {
+                body,
+                index,
+                count,
+                push_index,
+            } => (*index < *count).then(|| {
+                let mut instrs = Vec::new();
+                if *push_index {
+                    instrs.push(Instr::Push(Value::from(*index), from));
+                }
+                *index += 1;
+                instrs.extend(body.gen_instrs(from));
+                instrs
+            }),
+            Frame::Each {
+                body,
+                items,
+                index,
+                push_index,
+            } => items.next().map(|item| {
+                let mut instrs = vec![Instr::Push(item, from)];
+                if *push_index {
+                    instrs.push(Instr::Push(Value::from(*index), from));
+                }
+                *index += 1;
+                instrs.extend(body.gen_instrs(from));
+                instrs
+            }),
+            Frame::Lines { body } => {
+                let input = self.scope.borrow().input();
+                utils::read_input_line(&input)?.map(|line| {
+                    let mut instrs = vec![Instr::Push(Value::from(line), from)];
+                    instrs.extend(body.gen_instrs(from));
+                    instrs
+                })
+            }
+        };
+
+        match next {
+            Some(instrs) => self.queue_body(instrs, from),
+            None => self.pop_frame(),
+        }
+        Ok(())
+    }
+
+    // Skips the rest of the innermost loop's body, still running anything that restores the scope
+    fn exit_body(&mut self, keep_looping: bool) -> Status {
+        if self.frames.is_empty() {
+            let word = if keep_looping { "continue" } else { "break" };
+            return night_err!(Runtime, format!("'{word}' can only be used inside a loop."));
+        }
+
+        while let Some(instr) = self.instrs.pop_front() {
+            match instr {
+                Instr::Resume(i) if keep_looping => return self.resume(i),
+                Instr::Resume(_) => {
+                    self.pop_frame();
+                    return Ok(());
+                }
+                Instr::EndCallback
+                | Instr::GuardEnd(..)
+                | Instr::Unblock(..)
+                | Instr::Unbind(..) => self.exec_instr(instr)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Runs as `p1 { b1 } { { ...rest } cond } if`, so only the predicates that are needed are called
+    fn exec_intr_cond(&mut self, from: usize) -> Status {
+        let clauses = self.scope.borrow_mut().pop()?.as_fn()?;
+        let words = function::words(clauses.as_ref());
+        let as_fn = |instr: &Instr| match instr {
+            Instr::PushFunc(f, _) => Ok(f.clone()),
+            _ => night_err!(
+                UnsupportedType,
+                format!("'cond' clauses must be blocks, got '{instr}'.")
+            ),
+        };
+
+        match words.as_slice() {
+            [] => night_err!(Runtime, "No 'cond' clause matched."),
+            [default] => {
+                self.exec_fn(as_fn(default)?.gen_instrs(from), from);
+                Ok(())
+            }
+            [pred, body, rest @ ..] => {
+                let rest = vec![
+                    Instr::PushFunc(Rc::new(BlockFunc::from(rest.to_vec())), from),
+                    Instr::Intrinsic(Intr::Cond, from),
+                ];
+                let mut instrs = as_fn(pred)?.gen_instrs(from);
+                instrs.push(Instr::PushFunc(as_fn(body)?, from));
+                instrs.push(Instr::PushFunc(Rc::new(BlockFunc::from(rest)), from));
+                instrs.push(Instr::Intrinsic(Intr::If, from));
+                self.exec_fn(instrs, from);
+                Ok(())
+            }
+        }
+    }
+
+    fn exec_intr_case(&mut self, from: usize) -> Status {
+        let mut s = self.scope.borrow_mut();
+        let clauses = pattern::parse_clauses(&function::words(s.pop()?.as_fn()?.as_ref()))?;
+        let value = s.pop()?;
+
+        for (pattern, body) in clauses.arms {
+            let mut binds = Vec::new();
+            if pattern.matches(&value, &mut binds) {
+                // The bindings only last for the clause, so the caller's registers are untouched
+                let saved = binds
+                    .into_iter()
+                    .map(|(reg, v)| (reg.clone(), s.bind_reg(reg, v)))
+                    .collect();
+                drop(s);
+                let mut instrs = body.gen_instrs(from);
+                instrs.push(Instr::Unbind(saved, from));
+                self.exec_fn(instrs, from);
+                return Ok(());
+            }
+        }
+
+        // The default block receives the value that was not matched
+        match clauses.default {
+            Some(default) => {
+                s.push(value);
+                drop(s);
+                self.exec_fn(default.gen_instrs(from), from);
+                Ok(())
+            }
+            None => night_err!(Runtime, format!("No 'case' clause matched '{value}'.")),
+        }
+    }
+
     fn exec_intr_defr(&mut self, defr_span: usize) -> Status {
         let scope = self.scope.clone();
         let mut s = scope.borrow_mut();
@@ -584,6 +1172,60 @@ impl Night {
     }
 }
 
+// Ignores spans, so that functions can be compared structurally
+impl PartialEq for Instr {
+    fn eq(&self, other: &Self) -> bool {
+        use Instr::*;
+
+        match (self, other) {
+            (Push(a, _), Push(b, _)) => a == b,
+            (PushFunc(a, _), PushFunc(b, _)) => function::fn_eq(a.as_ref(), b.as_ref()),
+            (PushSym(a, reg_a, _), PushSym(b, reg_b, _)) => a == b && reg_a == reg_b,
+            (Op(a, _), Op(b, _)) => a == b,
+            (Internal(a, _), Internal(b, _)) => a == b,
+            (Intrinsic(a, _), Intrinsic(b, _)) => a == b,
+            (Guard(a, _), Guard(b, _))
+            | (GuardEnd(a, _), GuardEnd(b, _))
+            | (Block(a, _), Block(b, _))
+            | (Unblock(a, _), Unblock(b, _)) => a == b,
+            (ArrayStart(_), ArrayStart(_))
+            | (ArrayEnd(_), ArrayEnd(_))
+            | (MapStart(_), MapStart(_))
+            | (MapEnd(_), MapEnd(_))
+            | (EndCallback, EndCallback) => true,
+            (Resume(_), Resume(_)) => true,
+            (Unbind(a, _), Unbind(b, _)) => a.iter().map(|(r, _)| r).eq(b.iter().map(|(r, _)| r)),
+            _ => false,
+        }
+    }
+}
+
+// Renders the instruction as the Night source that would produce it
+impl Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instr::Push(v, _) => write!(f, "{}", v.repr()),
+            Instr::PushFunc(func, _) => write!(f, "{}", func.source()),
+            Instr::PushSym(s, false, _) => write!(f, "{s}"),
+            Instr::PushSym(s, true, _) => write!(f, "${s}"),
+            Instr::Op(o, _) => write!(f, "{}", o.get_glyph()),
+            Instr::Internal(b, _) => write!(f, "{}", b.name()),
+            Instr::Intrinsic(i, _) => write!(f, "{}", i.name()),
+            Instr::Guard(syms, _) => write!(f, "({})", syms.join(" ")),
+            Instr::Block(syms, _) => write!(f, ":{} |", syms.join(" :")),
+            Instr::ArrayStart(_) => write!(f, "["),
+            Instr::ArrayEnd(_) => write!(f, "]"),
+            Instr::MapStart(_) => write!(f, "#{{"),
+            Instr::MapEnd(_) => write!(f, "}}"),
+            Instr::GuardEnd(..)
+            | Instr::Unblock(..)
+            | Instr::EndCallback
+            | Instr::Resume(_)
+            | Instr::Unbind(..) => Ok(()),
+        }
+    }
+}
+
 impl Debug for Instr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -598,6 +1240,15 @@ impl Debug for Instr {
             Instr::GuardEnd(syms, _) => write!(f, "<guard_end: {syms:?}>"),
             Instr::Block(syms, _) => write!(f, "<block: {syms:?}>"),
             Instr::Unblock(syms, _) => write!(f, "<unblock: {syms:?}>"),
+            Instr::ArrayStart(_) => write!(f, "<array>"),
+            Instr::ArrayEnd(_) => write!(f, "<array_end>"),
+            Instr::MapStart(_) => write!(f, "<map>"),
+            Instr::MapEnd(_) => write!(f, "<map_end>"),
+            Instr::Resume(_) => write!(f, "<resume>"),
+            Instr::Unbind(saved, _) => {
+                let regs = saved.iter().map(|(r, _)| r).collect::<Vec<_>>();
+                write!(f, "<unbind: {regs:?}>")
+            }
             Instr::EndCallback => unreachable!(),
         }
     }
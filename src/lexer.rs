@@ -4,16 +4,23 @@ use std::str::CharIndices;
 
 use crate::builtin::{Operator, OP_MAP};
 use crate::utils;
-use crate::utils::error::{lex_err, Span};
+use crate::utils::error::{lex_err, NightError, Span, Status};
+use crate::utils::output::{self, Writer};
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Token {
-    /// `15`, `-3`
+    /// `15`, `-3`, `.5`, `1e9`, `0xFF`, `1_000`, `3f64`
     Number(Rc<str>),
     /// `$x`, `$_for_i`
     Register(Rc<str>),
-    /// `"hello world"`, `:goodbye`, `"hi"`, `'a`
+    /// `"hello world"`, `:goodbye`, `"hi\n"`, `r"raw"`, `'a`
     String(Rc<str>),
+    /// `"x is {$x}"`, any string literal referencing a register
+    Template(Rc<str>),
+    /// `true`, `false`
+    Bool(bool),
+    /// `nil`
+    Nil,
     /// `for`, `print`, `add`, etc.
     Symbol(Rc<str>),
     /// `+`, `!=`, `.`, etc
@@ -33,10 +40,14 @@ pub enum Token {
     OpenCurly,
     /// `}`
     CloseCurly,
+    /// `#{`
+    OpenMap,
     /// `[`
     OpenBracket,
     /// `]`
     CloseBracket,
+    /// `( x q -- x )`, a stack effect declaration
+    Effect(Rc<str>),
     /// `(`
     OpenParen,
     /// `)`
@@ -52,6 +63,9 @@ pub struct Lexer<'a> {
     chars: Peekable<CharIndices<'a>>,
     line: usize,
     tokens: Vec<LexTok>,
+    err: Writer,
+    // The first error, after which lexing stops
+    error: Option<NightError>,
 }
 
 /// Shorthand for writing out Some((tok, Span::span(/* ... */)))
@@ -81,19 +95,30 @@ impl<'a> Lexer<'a> {
             chars: input.char_indices().peekable(),
             line: 0,
             tokens: Vec::new(),
+            err: output::stdout(),
+            error: None,
         }
     }
 
+    /// Report lex errors to `err` instead of stdout
+    pub fn with_errors(mut self, err: Writer) -> Self {
+        self.err = err;
+        self
+    }
+
     #[inline]
     fn next_if(&mut self, f: impl FnOnce(char) -> bool) -> Option<(usize, char)> {
         self.chars.next_if(|&(_, c)| f(c))
     }
 
-    /// Entry function for tokenization
-    pub fn tokenize(mut self) -> Vec<LexTok> {
+    /// Entry function for tokenization, failing once an error has been reported
+    pub fn tokenize(mut self) -> Status<Vec<LexTok>> {
         while let Some(tok) = self.consume_token() {
             self.tokens.push(tok);
         }
+        if let Some(e) = self.error {
+            return Err(e);
+        }
 
         self.tokens.push((
             Token::EOF,
@@ -106,7 +131,7 @@ impl<'a> Lexer<'a> {
             ),
         ));
 
-        self.tokens
+        Ok(self.tokens)
     }
 
     #[inline]
@@ -114,11 +139,12 @@ impl<'a> Lexer<'a> {
         let (start, chr) = self.chars.next()?;
         match chr {
             '0'..='9' => self.consume_number(start),
+            'r' if self.is_raw_string(start) => self.consume_raw_string(start),
             '_' | 'a'..='z' | 'A'..='Z' => self.consume_symbol(start),
             c if c.is_whitespace() => self.consume_whitespace(c, start),
             c if c.is_ascii_punctuation() => self.maybe_op(c, start),
             _ => {
-                lex_err!("LexError: Unrecognized token."; self.input, start, 1, self.line => self.line)
+                lex_err!("Unrecognized token."; self, start, 1, self.line => self.line)
             }
         }
     }
@@ -128,14 +154,22 @@ impl<'a> Lexer<'a> {
             return self.skip_comment();
         } else if chr == '-' && self.next_if(|c| c == '>').is_some() {
             return lex_tok!(Token::DefineSym, self, start, 2, 0);
-        } else if chr == '-' && self.next_if(|c| c.is_ascii_digit()).is_some() {
+        } else if (chr == '-' || chr == '.') && self.starts_number(start + 1, chr == '-') {
             return self.consume_number(start);
+        // Conversions like `>quot`
+        } else if chr == '>'
+            && self
+                .chars
+                .peek()
+                .is_some_and(|&(_, c)| c.is_ascii_alphabetic())
+        {
+            return self.consume_symbol(start);
         // This uses `peek` instead of `next_if` in order to avoid issues with the 1st char of the word being consumed before `calculate_var_bounds` is called.
         } else if chr == ':'
             && self
                 .chars
                 .peek()
-                .map_or(false, |&(_, c)| utils::valid_symbol_chr(c))
+                .is_some_and(|&(_, c)| utils::valid_symbol_chr(c))
         {
             return self.consume_word(start);
         }
@@ -147,15 +181,19 @@ impl<'a> Lexer<'a> {
             '!' => lex_tok!(Token::Exclamation, self, start, 1, 0),
             '@' => lex_tok!(Token::AtSign, self, start, 1, 0),
             '|' => lex_tok!(Token::Pipe, self, start, 1, 0),
+            '#' if self.next_if(|c| c == '{').is_some() => {
+                lex_tok!(Token::OpenMap, self, start, 2, 0)
+            }
             '[' => lex_tok!(Token::OpenBracket, self, start, 1, 0),
             ']' => lex_tok!(Token::CloseBracket, self, start, 1, 0),
             '{' => lex_tok!(Token::OpenCurly, self, start, 1, 0),
             '}' => lex_tok!(Token::CloseCurly, self, start, 1, 0),
+            '(' if self.is_effect(start) => self.consume_effect(start),
             '(' => lex_tok!(Token::OpenParen, self, start, 1, 0),
             ')' => lex_tok!(Token::CloseParen, self, start, 1, 0),
             _ if OP_MAP.contains_key(&self.input[start..start + 1]) => self.consume_op(start),
             _ => {
-                lex_err!("LexError: Unrecognized token."; self.input, start, 1, self.line => self.line)
+                lex_err!("Unrecognized token."; self, start, 1, self.line => self.line)
             }
         }
     }
@@ -170,16 +208,60 @@ impl<'a> Lexer<'a> {
         self.consume_token()
     }
 
+    /// Whether the input at `i` begins a number, allowing a leading `.` (as in `.5`) if `dot` is set
+    #[inline]
+    fn starts_number(&self, i: usize, dot: bool) -> bool {
+        let bytes = &self.input.as_bytes()[i.min(self.input.len())..];
+        match bytes {
+            [b'0'..=b'9', ..] => true,
+            [b'.', b'0'..=b'9', ..] => dot,
+            _ => false,
+        }
+    }
+
+    // Validation of digits, suffixes and ranges happens when the literal is parsed in `Night::build_instr`,
+    // so anything alphanumeric directly following a number is kept as part of the same token.
     fn consume_number(&mut self, start: usize) -> Option<LexTok> {
-        let mut end = start + 1;
-        let mut found_decimal = false;
-        while let Some((_, c)) = self.next_if(|c| c.is_ascii_digit() || c == '.' && !found_decimal)
-        {
-            if c == '.' {
-                found_decimal = true;
-            }
+        let bytes = self.input.as_bytes();
+        let is_digit = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+        let mut end = start;
+        if bytes[end] == b'-' {
             end += 1;
         }
+
+        let radix = bytes.len() > end + 1
+            && bytes[end] == b'0'
+            && matches!(bytes[end + 1], b'x' | b'b' | b'o');
+        if radix {
+            end += 2;
+            while end < bytes.len() && is_digit(bytes[end]) {
+                end += 1;
+            }
+        } else {
+            let is_dec = |b: u8| b.is_ascii_digit() || b == b'_';
+            while end < bytes.len() && is_dec(bytes[end]) {
+                end += 1;
+            }
+            // `1.` is the number 1 followed by `.`, a decimal point must be followed by a digit
+            if self.starts_number(end, true) && bytes[end] == b'.' {
+                end += 1;
+                while end < bytes.len() && is_dec(bytes[end]) {
+                    end += 1;
+                }
+            }
+            if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+                let sign = matches!(bytes.get(end + 1), Some(b'+' | b'-')) as usize;
+                if self.starts_number(end + 1 + sign, false) {
+                    end += 1 + sign;
+                }
+            }
+            // Suffix (`i64`, `f64`) or an invalid trailing identifier
+            while end < bytes.len() && is_digit(bytes[end]) {
+                end += 1;
+            }
+        }
+
+        while self.chars.next_if(|&(i, _)| i < end).is_some() {}
         lex_tok!(Token::Number, start, end, self, start, end - start, 0)
     }
 
@@ -194,14 +276,48 @@ impl<'a> Lexer<'a> {
 
     // The pass to convert matching symbols to built ins and operators occurs prior to execution
     fn consume_symbol(&mut self, start: usize) -> Option<LexTok> {
-        let (start, end) = self.calculate_var_bounds(start);
-        lex_tok!(Token::Symbol, start, end, self, start, end - start, 0)
+        let (start, mut end) = self.calculate_var_bounds(start);
+        // Field accessors like `point.x` are one word, but `x.` is still `x` followed by a dup
+        while self.input[end..].starts_with('.')
+            && self.input[end + 1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        {
+            self.chars.next();
+            end += 1;
+            while self.next_if(utils::valid_symbol_chr).is_some() {
+                end += 1;
+            }
+        }
+
+        // Predicates like `some?` may end in a `?`, unless it is the start of a `?@` singleton.
+        // Conversions like `quot>` may end in a `>` as long as the word ends there.
+        let rest = &self.input[end..];
+        let ends_word = |s: &str| {
+            s.chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace() || "})]".contains(c))
+        };
+        let has_suffix = match rest.chars().next() {
+            Some('?') => !rest.starts_with("?@"),
+            Some('>') => ends_word(&rest[1..]),
+            _ => false,
+        };
+        if has_suffix {
+            self.chars.next();
+            end += 1;
+        }
+
+        match &self.input[start..end] {
+            "true" => lex_tok!(Token::Bool(true), self, start, end - start, 0),
+            "false" => lex_tok!(Token::Bool(false), self, start, end - start, 0),
+            "nil" => lex_tok!(Token::Nil, self, start, end - start, 0),
+            _ => lex_tok!(Token::Symbol, start, end, self, start, end - start, 0),
+        }
     }
 
     fn consume_register(&mut self, start: usize) -> Option<LexTok> {
         let (start, end) = self.calculate_var_bounds(start);
         if end - start == 1 {
-            lex_err!("LexError: Missing identifier for register."; self.input, start, 1, self.line => self.line);
+            lex_err!("Missing identifier for register."; self, start, 1, self.line => self.line);
         }
 
         lex_tok!(Token::Register, start + 1, end, self, start, end - start, 0)
@@ -213,30 +329,179 @@ impl<'a> Lexer<'a> {
     }
 
     fn consume_string(&mut self, start: usize) -> Option<LexTok> {
-        let mut valid_str = false;
+        let mut buf = String::new();
+        // Where `\{` and `\}` were written, as those braces never start or end a template
+        let mut escaped = vec![];
         let mut lines = 0;
-        let mut end = start + 2;
-        while let Some((_, chr)) = self.chars.next() {
-            if chr == '"' {
-                valid_str = true;
-                break;
-            } else if chr == '\n' {
-                lines += 1;
+        let end = loop {
+            match self.chars.next() {
+                Some((i, '"')) => break i + 1,
+                Some((i, '\\')) => {
+                    let chr = self.consume_escape(i, self.line + lines)?;
+                    if chr == '{' || chr == '}' {
+                        escaped.push(buf.len());
+                    }
+                    buf.push(chr);
+                }
+                Some((_, chr)) => {
+                    if chr == '\n' {
+                        lines += 1;
+                    }
+                    buf.push(chr);
+                }
+                None => {
+                    let span = self.input.len() + 1 - start;
+                    lex_err!("String not terminated."; self, start, span, self.line => self.line + lines);
+                }
             }
-            end += 1;
+        };
+
+        let tok = if buf.match_indices("{$").any(|(i, _)| !escaped.contains(&i)) {
+            // Escaped braces are doubled, which `format` reads as literal braces
+            for i in escaped.into_iter().rev() {
+                buf.insert(i, buf[i..].chars().next().unwrap());
+            }
+            lex_tok!(Token::Template(buf.into()), self, start, end - start, lines)
+        } else {
+            lex_tok!(Token::String(buf.into()), self, start, end - start, lines)
+        };
+        self.line += lines;
+        tok
+    }
+
+    // `start` is the index of the backslash, so that errors can point at the whole sequence
+    fn consume_escape(&mut self, start: usize, line: usize) -> Option<char> {
+        let chr = match self.chars.next() {
+            Some((_, 'n')) => '\n',
+            Some((_, 't')) => '\t',
+            Some((_, 'r')) => '\r',
+            Some((_, '0')) => '\0',
+            Some((_, '"')) => '"',
+            Some((_, '\'')) => '\'',
+            Some((_, '\\')) => '\\',
+            Some((_, '{')) => '{',
+            Some((_, '}')) => '}',
+            Some((_, 'u')) => return self.consume_unicode_escape(start, line),
+            Some((i, c)) => {
+                let len = i + c.len_utf8() - start;
+                lex_err!("Unknown escape sequence."; self, start, len, line => line)
+            }
+            None => {
+                lex_err!("String not terminated."; self, start, 1, line => line)
+            }
+        };
+        Some(chr)
+    }
+
+    fn consume_unicode_escape(&mut self, start: usize, line: usize) -> Option<char> {
+        if self.next_if(|c| c == '{').is_none() {
+            lex_err!("Expected '{' after '\\u'."; self, start, 2, line => line);
         }
 
-        let span = end - start;
+        let mut code = String::new();
+        let end = loop {
+            match self.chars.next() {
+                Some((i, '}')) => break i + 1,
+                Some((_, c)) if c.is_ascii_hexdigit() && code.len() < 6 => code.push(c),
+                Some((i, c)) => {
+                    let len = i + c.len_utf8() - start;
+                    lex_err!("Invalid unicode escape, expected up to 6 hex digits followed by '}'."; self, start, len, line => line)
+                }
+                None => {
+                    lex_err!("String not terminated."; self, start, 3 + code.len(), line => line)
+                }
+            }
+        };
 
-        if !valid_str {
-            lex_err!("LexError: String not terminated."; self.input, start, span, self.line => self.line + lines);
+        let chr = u32::from_str_radix(&code, 16).ok().and_then(char::from_u32);
+        if chr.is_none() {
+            lex_err!("Invalid unicode codepoint."; self, start, end - start, line => line);
         }
+        chr
+    }
 
-        let tok = lex_tok!(Token::String, start + 1, end - 1, self, start, span, lines);
+    #[inline]
+    fn is_raw_string(&self, start: usize) -> bool {
+        self.input[start + 1..]
+            .trim_start_matches('#')
+            .starts_with('"')
+    }
+
+    /// `r"..."` strings have no escapes. Any number of `#` can surround the quotes (`r#"..."#`)
+    /// so that the string itself can contain `"`.
+    fn consume_raw_string(&mut self, start: usize) -> Option<LexTok> {
+        let hashes = self.input[start + 1..]
+            .chars()
+            .take_while(|&c| c == '#')
+            .count();
+        let body_start = start + hashes + 2;
+        while self.chars.next_if(|&(i, _)| i < body_start).is_some() {}
+
+        let terminator = format!("\"{}", "#".repeat(hashes));
+        let Some(len) = self.input[body_start..].find(&terminator) else {
+            let lines = self.input[body_start..].matches('\n').count();
+            let span = self.input.len() + 1 - start;
+            lex_err!("Raw string not terminated."; self, start, span, self.line => self.line + lines);
+        };
+
+        let end = body_start + len + terminator.len();
+        while self.chars.next_if(|&(i, _)| i < end).is_some() {}
+
+        let lines = self.input[body_start..body_start + len]
+            .matches('\n')
+            .count();
+        let tok = lex_tok!(
+            Token::String,
+            body_start,
+            body_start + len,
+            self,
+            start,
+            end - start,
+            lines
+        );
         self.line += lines;
         tok
     }
 
+    // Byte index of the `)` matching the `(` at `start`, if it is on the same line
+    fn matching_paren(&self, start: usize) -> Option<usize> {
+        let mut depth = 0;
+        for (i, c) in self.input[start..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 1 => return Some(start + i),
+                ')' => depth -= 1,
+                '\n' => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // A parenthesized group is an effect instead of a guard if it contains a `--`,
+    // which has to be checked before it would be mistaken for a comment
+    fn is_effect(&self, start: usize) -> bool {
+        self.matching_paren(start).is_some_and(|end| {
+            self.input[start + 1..end]
+                .split_whitespace()
+                .any(|w| w == "--")
+        })
+    }
+
+    fn consume_effect(&mut self, start: usize) -> Option<LexTok> {
+        let end = self.matching_paren(start)? + 1;
+        while self.chars.next_if(|&(i, _)| i < end).is_some() {}
+        lex_tok!(
+            Token::Effect,
+            start + 1,
+            end - 1,
+            self,
+            start,
+            end - start,
+            0
+        )
+    }
+
     fn consume_op(&mut self, start: usize) -> Option<LexTok> {
         let mut end = start + 1;
         let inp = self.input.clone();
@@ -252,15 +517,16 @@ impl<'a> Lexer<'a> {
     }
 
     fn consume_char_lit(&mut self, start: usize) -> Option<LexTok> {
-        if self.chars.next().is_none() {
-            lex_err!("LexError: Missing following char identifier."; self.input, start, 1, self.line => self.line);
-        }
+        let Some((_, chr)) = self.chars.next() else {
+            lex_err!("Missing following char identifier."; self, start, 1, self.line => self.line);
+        };
 
-        lex_tok!(Token::String, start + 1, start + 2, self, start, 1, 0)
+        let end = start + 1 + chr.len_utf8();
+        lex_tok!(Token::String, start + 1, end, self, start, 1, 0)
     }
 
     fn skip_comment(&mut self) -> Option<LexTok> {
-        while let Some((i, tok)) = self.chars.next() {
+        for (i, tok) in self.chars.by_ref() {
             if tok == '\n' {
                 let t = lex_tok!(Token::Newline, self, i, 1, 0);
                 self.line += 1;
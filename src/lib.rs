@@ -1,4 +1,5 @@
 pub mod builtin;
+pub mod checker;
 pub mod interpreter;
 pub mod lexer;
 pub mod scope;
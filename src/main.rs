@@ -1,8 +1,11 @@
 use std::io::{self, Write};
+use std::{env, fs, process};
 
 use night::interpreter::Night;
 use night::lexer::Lexer;
 use night::utils;
+use night::utils::error::{NightError, Status};
+use night::utils::process::Process;
 
 fn main() {
     // Simulate execution of a program for testing
@@ -50,16 +53,15 @@ fn main() {
     1 11 { $I 2 * } for_range
     "#;*/
 
-    println!("Night CLI. Use `halt` to terminate.");
     let mut night = Night::new();
-    utils::define_fns(
+    or_exit(utils::define_fns(
         &mut night,
         r#"
         -> rotn 1 - {} { { dip : } curry } swpd loop ?
-        -> over2 pick pick
-        -> dip (top) : $top! :top | ? $top
-        -> dip2 : dip@ dip
-        -> dip3 : dip2@ dip
+        -> over2 ( x y z -- x y z x y ) pick pick
+        -> dip ( x block -- x ) (top) : $top! :top | ? $top
+        -> dip2 ( x y block -- x y ) : dip@ dip
+        -> dip3 ( x y z block -- x y z ) : dip2@ dip
         -> keep over ?@ dip
         -> keep2 dup2@ dip dip2
         -> keep3 dup3@ dip dip3
@@ -70,28 +72,88 @@ fn main() {
         -> when : ?@ ;@ if
         -> unless : ;@ ?@ if
         -> choose 3 rotn ;@ nip@ if
+        -> map_some over some? ?@ ;@ if
         "#,
-    );
+    ));
+
+    let args = env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        Some("check") => {
+            let Some(path) = args.get(2) else {
+                eprintln!("Usage: night check <file>");
+                process::exit(2);
+            };
+            check(&mut night, path);
+            return;
+        }
+        Some(path) => {
+            let script_args = args[2..].to_vec();
+            night.set_process(Process::from_env(Some(path.to_string()), script_args));
+            run(&mut night, path);
+            return;
+        }
+        None => night.set_process(Process::from_env(None, vec![])),
+    }
 
+    println!("Night CLI. Use `halt` to terminate.");
     let mut input;
     loop {
         print!(">> ");
         io::stdout().flush().unwrap();
 
         input = String::new();
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut input)
             .expect("Error reading line");
 
+        // End of input, e.g. Ctrl-D
+        if read == 0 {
+            println!();
+            break;
+        }
+
         match input.as_ref() {
             "halt\n" => break,
             line => {
-                let lex = Lexer::new(line);
-                night.push_new_code(line, lex.tokenize());
-                night.exec();
+                let lex = Lexer::new(line).with_errors(night.out());
+                or_exit(night.push_new_code(line, or_exit(lex.tokenize())));
+                or_exit(night.exec());
                 // println!("--- STACK ---");
                 // println!("{}", night.get_scope().borrow());
             }
         }
     }
 }
+
+// Errors are reported before they get here, so all that's left is to stop
+fn or_exit<T>(status: Status<T>) -> T {
+    status.unwrap_or_else(|e| match e {
+        NightError::Exit(code) => process::exit(code),
+        _ => process::exit(-1),
+    })
+}
+
+fn read_source(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Could not read '{path}': {e}");
+        process::exit(2);
+    })
+}
+
+// Runs a script, leaving stdin free for the program itself
+fn run(night: &mut Night, path: &str) {
+    let code = read_source(path);
+    let lex = Lexer::new(&code).with_errors(night.out());
+    or_exit(night.push_new_code(&code, or_exit(lex.tokenize())));
+    or_exit(night.exec());
+}
+
+// Parses the file and runs the static checks without executing it, exiting on the first error
+fn check(night: &mut Night, path: &str) {
+    let code = read_source(path);
+
+    night.set_strict_types(true);
+    let lex = Lexer::new(&code).with_errors(night.out());
+    or_exit(night.push_new_code(&code, or_exit(lex.tokenize())));
+    println!("No errors found in '{path}'.");
+}
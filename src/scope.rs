@@ -1,10 +1,16 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{self, Display};
 use std::rc::Rc;
 
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
 use crate::utils::error::{night_err, NightError, Status};
-use crate::utils::function::Generable;
-use crate::value::Value;
+use crate::utils::function::{self, Generable};
+use crate::utils::output::{self, Reader, Writer};
+use crate::utils::process::Process;
+use crate::value::{Map, Value};
 
 #[derive(Clone)]
 pub enum StackVal {
@@ -34,12 +40,24 @@ impl StackVal {
 impl Display for StackVal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Function(_) => write!(f, "<function>"),
+            Self::Function(func) => write!(f, "{}", func.source()),
             Self::Value(v) => write!(f, "{v}"),
         }
     }
 }
 
+impl PartialEq for StackVal {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Value(left), Self::Value(right)) => left == right,
+            (Self::Function(left), Self::Function(right)) => {
+                Rc::ptr_eq(left, right) || function::fn_eq(left.as_ref(), right.as_ref())
+            }
+            _ => false,
+        }
+    }
+}
+
 impl From<Value> for StackVal {
     fn from(value: Value) -> Self {
         Self::Value(value)
@@ -63,11 +81,11 @@ enum SymbolType {
     Register(String),
 }
 
-impl ToString for SymbolType {
-    fn to_string(&self) -> String {
+impl Display for SymbolType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Symbol(s) => s.clone(),
-            Self::Register(s) => format!("${s}"),
+            Self::Symbol(s) => write!(f, "{s}"),
+            Self::Register(s) => write!(f, "${s}"),
         }
     }
 }
@@ -122,6 +140,14 @@ pub struct ScopeInternal {
     block: HashSet<String>,
     env: HashMap<SymbolType, StackVal>,
     register_trace: HashMap<String, RegTrace>,
+    // Stack heights at each currently open `[` or `#{`
+    collection_marks: Vec<usize>,
+    input: Reader,
+    out: Writer,
+    err: Writer,
+    process: Rc<Process>,
+    // Shared with child scopes, so they continue the same sequence
+    rng: Rc<RefCell<StdRng>>,
 }
 
 impl ScopeInternal {
@@ -132,23 +158,69 @@ impl ScopeInternal {
             block: HashSet::new(),
             env: HashMap::new(),
             register_trace: HashMap::new(),
+            collection_marks: Vec::new(),
+            input: output::stdin(),
+            out: output::stdout(),
+            err: output::stderr(),
+            process: Rc::new(Process::default()),
+            rng: Rc::new(RefCell::new(StdRng::from_entropy())),
         }
     }
 
+    /// Where `read_line`, `read_all` and `lines` read from
+    pub fn input(&self) -> Reader {
+        self.input.clone()
+    }
+
+    pub fn set_input(&mut self, input: Reader) {
+        self.input = input;
+    }
+
+    /// Where `print`, the dumps, errors and warnings write to
+    pub fn out(&self) -> Writer {
+        self.out.clone()
+    }
+
+    /// Where `eprint` writes to
+    pub fn err(&self) -> Writer {
+        self.err.clone()
+    }
+
+    pub fn set_output(&mut self, out: Writer, err: Writer) {
+        self.out = out;
+        self.err = err;
+    }
+
+    /// The arguments, environment and script path visible to the program
+    pub fn process(&self) -> Rc<Process> {
+        self.process.clone()
+    }
+
+    pub fn set_process(&mut self, process: Process) {
+        self.process = Rc::new(process);
+    }
+
+    /// The random number generator behind `rand`, `shuffle` and friends
+    pub fn rng(&self) -> Rc<RefCell<StdRng>> {
+        self.rng.clone()
+    }
+
     fn add_trace(&mut self, g: String) {
         if !self.register_trace.contains_key(&g) {
             self.register_trace.insert(g.clone(), RegTrace::new());
         }
     }
 
-    pub fn dump_symbols(&self) {
-        println!("--- SYMBOL DMP: ---");
+    pub fn dump_symbols(&self) -> Status {
+        let mut out = self.out.borrow_mut();
+        writeln!(out, "--- SYMBOL DMP: ---")?;
         for (key, val) in &self.env {
             if let SymbolType::Symbol(s) = key {
-                println!("{s}: {val}");
+                writeln!(out, "{s}: {val}")?;
             }
         }
-        println!("-------------------");
+        writeln!(out, "-------------------")?;
+        Ok(())
     }
 
     // For now this returns a `Status`, as I might use it in the future.
@@ -241,17 +313,72 @@ impl ScopeInternal {
         self.stack.push(StackVal::Value(val));
     }
 
+    pub fn open_collection(&mut self) {
+        self.collection_marks.push(self.stack.len());
+    }
+
+    // Takes every value pushed since the matching `open_collection`
+    fn take_collection(&mut self, kind: &str) -> Status<Vec<Value>> {
+        let mark = match self.collection_marks.pop() {
+            Some(m) => m,
+            None => return night_err!(Syntax, format!("Unbalanced {kind}.")),
+        };
+        if mark > self.stack.len() {
+            return night_err!(
+                Runtime,
+                format!("{kind} body consumed values from outside of the {kind}.")
+            );
+        }
+
+        self.stack
+            .split_off(mark)
+            .into_iter()
+            .map(StackVal::as_value)
+            .collect()
+    }
+
+    /// Collects every value pushed since the matching `open_collection` into a single array
+    pub fn close_array(&mut self) -> Status {
+        let items = self.take_collection("array")?;
+        self.push_value(Value::from(items));
+        Ok(())
+    }
+
+    /// Pairs up every value pushed since the matching `open_collection` into the entries of a map
+    pub fn close_map(&mut self) -> Status {
+        let items = self.take_collection("map")?;
+        if items.len() % 2 != 0 {
+            return night_err!(
+                Runtime,
+                format!(
+                    "Map literal has a key without a value ({} items).",
+                    items.len()
+                )
+            );
+        }
+
+        let mut map = Map::new();
+        let mut items = items.into_iter();
+        while let (Some(k), Some(v)) = (items.next(), items.next()) {
+            map.insert(k, v);
+        }
+        self.push_value(Value::from(map));
+        Ok(())
+    }
+
     pub fn stack_len(&self) -> usize {
         self.stack.len()
     }
 
     pub fn def_sym(&mut self, sym: String, s: StackVal) -> Status {
-        let sym = SymbolType::Symbol(sym);
-        if self.env.contains_key(&sym) {
-            night_err!(SymbolRedefinition, sym.to_string())
-        } else {
-            self.env.insert(sym, s);
-            Ok(())
+        use std::collections::hash_map::Entry;
+
+        match self.env.entry(SymbolType::Symbol(sym)) {
+            Entry::Occupied(e) => night_err!(SymbolRedefinition, e.key().to_string()),
+            Entry::Vacant(e) => {
+                e.insert(s);
+                Ok(())
+            }
         }
     }
 
@@ -279,6 +406,20 @@ impl ScopeInternal {
         Ok(s)
     }
 
+    /// Binds a register without the guard checks of `def_reg`, returning its previous value so it
+    /// can be put back with `restore_reg`
+    pub fn bind_reg(&mut self, name: String, s: StackVal) -> Option<StackVal> {
+        self.env.insert(SymbolType::Register(name), s)
+    }
+
+    pub fn restore_reg(&mut self, name: String, prev: Option<StackVal>) {
+        let reg = SymbolType::Register(name);
+        match prev {
+            Some(v) => self.env.insert(reg, v),
+            None => self.env.remove(&reg),
+        };
+    }
+
     pub fn undef_sym(&mut self, sym: String) -> Status<StackVal> {
         let sym = SymbolType::Symbol(sym);
         self.env
@@ -338,10 +479,7 @@ impl ScopeInternal {
 impl Display for ScopeInternal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for val in &self.stack {
-            match val {
-                StackVal::Value(v) => writeln!(f, "{v}")?,
-                StackVal::Function(_) => writeln!(f, "<function>")?,
-            }
+            writeln!(f, "{val}")?;
         }
 
         Ok(())
@@ -352,10 +490,7 @@ impl From<Vec<StackVal>> for ScopeInternal {
     fn from(value: Vec<StackVal>) -> Self {
         Self {
             stack: value,
-            guard: HashSet::new(),
-            block: HashSet::new(),
-            env: HashMap::new(),
-            register_trace: HashMap::new(),
+            ..Self::create()
         }
     }
 }
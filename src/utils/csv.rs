@@ -0,0 +1,137 @@
+use crate::utils::error::{night_err, Status};
+
+/// Splits CSV text into rows of fields. Quoted fields may contain the delimiter, newlines and
+/// `""` for a literal quote. Both `\n` and `\r\n` end a row, and a trailing newline is optional.
+pub fn parse(src: &str, delim: char) -> Status<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    // Whether the current field was quoted, since `""` is an empty field rather than none at all
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = src.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => {
+                quoted = true;
+                let start = line;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            line += usize::from(c == '\n');
+                            field.push(c);
+                        }
+                        None => {
+                            return night_err!(
+                                Runtime,
+                                format!("Invalid CSV at line {start}: unterminated quoted field.")
+                            )
+                        }
+                    }
+                }
+            }
+            c if c == delim => {
+                row.push(std::mem::take(&mut field));
+                quoted = false;
+            }
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                quoted = false;
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted || !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn quote(field: &str, delim: char) -> String {
+    if field.contains([delim, '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The inverse of `parse`, quoting only the fields that need it
+pub fn write(rows: &[Vec<String>], delim: char) -> String {
+    let mut out = String::new();
+    for row in rows {
+        let fields = row.iter().map(|f| quote(f, delim)).collect::<Vec<_>>();
+        out.push_str(&fields.join(&delim.to_string()));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|f| f.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn plain_fields() {
+        assert_eq!(
+            parse("a,b\r\nc,d\n", ',').unwrap(),
+            rows(&[&["a", "b"], &["c", "d"]])
+        );
+        assert_eq!(
+            parse("a;b\n;", ';').unwrap(),
+            rows(&[&["a", "b"], &["", ""]])
+        );
+        assert_eq!(parse("", ',').unwrap(), rows(&[]));
+    }
+
+    #[test]
+    fn quoted_fields() {
+        assert_eq!(
+            parse("\"a,b\",\"say \"\"hi\"\"\"\n", ',').unwrap(),
+            rows(&[&["a,b", "say \"hi\""]])
+        );
+        assert_eq!(
+            parse("\"multi\nline\",x\ny,z", ',').unwrap(),
+            rows(&[&["multi\nline", "x"], &["y", "z"]])
+        );
+        assert!(parse("a,\"open\nfield", ',').is_err());
+    }
+
+    #[test]
+    fn empty_quoted_fields() {
+        assert_eq!(parse("\"\"", ',').unwrap(), rows(&[&[""]]));
+        assert_eq!(parse("a\n\"\"", ',').unwrap(), rows(&[&["a"], &[""]]));
+        assert_eq!(parse("a,\"\"", ',').unwrap(), rows(&[&["a", ""]]));
+    }
+
+    #[test]
+    fn write_round_trips() {
+        let table = rows(&[
+            &["a,b", "say \"hi\""],
+            &["multi\nline", ""],
+            &["plain", "x"],
+        ]);
+        let text = write(&table, ',');
+        assert_eq!(
+            text,
+            "\"a,b\",\"say \"\"hi\"\"\"\n\"multi\nline\",\nplain,x\n"
+        );
+        assert_eq!(parse(&text, ',').unwrap(), table);
+    }
+}
@@ -3,6 +3,8 @@ use std::fmt::{self, Display};
 use std::num::{ParseFloatError, ParseIntError};
 use std::rc::Rc;
 
+use crate::utils::output::Writer;
+
 #[derive(Clone, Debug)]
 pub struct Span {
     code: Rc<str>,
@@ -23,6 +25,7 @@ impl Span {
         }
     }
 
+    #[allow(clippy::self_named_constructors)]
     pub fn span(
         code: Rc<str>,
         start: usize,
@@ -85,13 +88,12 @@ impl Span {
         let offset: usize = self.code.lines().take(self.line_start).map(str::len).sum();
         let left = self.start - offset - self.line_start;
 
-        let right;
-        if self.line_start == self.line_end {
-            right = left + self.len;
+        let right = if self.line_start == self.line_end {
+            left + self.len
         } else {
             let offset: usize = self.code.lines().take(self.line_end).map(str::len).sum();
-            right = self.start + self.len - offset - self.line_end;
-        }
+            self.start + self.len - offset - self.line_end
+        };
 
         (left, right)
     }
@@ -118,49 +120,65 @@ impl Display for Span {
     }
 }
 
-// Shorthand macro for calling crate::utils::error::error, used in `lexer.js`
+// Shorthand macro for calling crate::utils::error::error, used in `lexer.js`. Lexing stops at the
+// first error, which `Lexer::tokenize` then returns.
 macro_rules! lex_err {
-    ($msg:expr ; $code:expr, $start:expr, $len:expr, $line_start:expr => $line_end:expr) => {
+    ($msg:expr ; $lex:expr, $start:expr, $len:expr, $line_start:expr => $line_end:expr) => {{
+        let e = crate::utils::error::NightError::Lex($msg.to_string());
         crate::utils::error::error(
-            $msg,
-            crate::utils::error::Span::span($code.clone(), $start, $len, $line_start, $line_end),
-        )
-    };
+            &$lex.err,
+            &e,
+            crate::utils::error::Span::span($lex.input.clone(), $start, $len, $line_start, $line_end),
+        );
+        $lex.error = Some(e);
+        return None;
+    }};
 }
 
 pub(crate) use lex_err;
 
-pub fn error(msg: impl Display, span: Span) -> ! {
-    println!("{msg} {span}");
-    std::process::exit(-1)
+// Failing to report an error leaves nowhere to report that failure, so write errors are ignored.
+// Stopping is left to the caller, see `Night::fail`.
+pub fn error(err: &Writer, msg: impl Display, span: Span) {
+    let mut err = err.borrow_mut();
+    let _ = writeln!(err, "{msg} {span}");
+    let _ = err.flush();
 }
 
-pub fn warn(msg: impl Display, span: Span) {
-    println!("Warning: {msg} {span}")
+pub fn warn(err: &Writer, msg: impl Display, span: Span) {
+    let _ = writeln!(err.borrow_mut(), "Warning: {msg} {span}");
 }
 
-pub fn error_with_trace(msg: impl Display, span: Span, trace: Vec<Span>) -> ! {
-    println!("{msg} {span}");
+pub fn error_with_trace(err: &Writer, msg: impl Display, span: Span, trace: Vec<Span>) {
+    let mut err = err.borrow_mut();
+    let _ = writeln!(err, "{msg} {span}");
     for s in trace {
-        print!("Called from {s}");
+        let _ = write!(err, "Called from {s}");
     }
 
-    std::process::exit(-1);
+    let _ = err.flush();
 }
 
 #[derive(Clone, Debug)]
 pub enum NightError {
     Pass,
     Fail,
+    // The program called `exit` with this code
+    Exit(i32),
+    // An error that a nested run has already reported, see `Night::fail`
+    Reported(Box<NightError>),
     NothingToPop,
     NaN,
     ContextFail(String),
     Syntax(String),
+    Lex(String),
     UnsupportedType(String),
     UndefinedSymbol(String),
     SymbolRedefinition(String),
     Unimplemented(String),
     Runtime(String),
+    StackEffect(String),
+    Io(String),
     Warning(String),
     // TODO: whatever else I need
 }
@@ -186,15 +204,20 @@ impl Display for NightError {
         match self {
             Pass => unreachable!(),
             Fail => write!(f, "Error: Program failed."),
+            Exit(code) => write!(f, "Exited with code {code}."),
+            Reported(e) => write!(f, "{e}"),
             ContextFail(s) => write!(f, "Error: {s}"),
             NothingToPop => write!(f, "StackError: Missing value to pop."),
             NaN => write!(f, "TypeError: Not a valid number."),
             Syntax(s) => write!(f, "SyntaxError: {s}"),
+            Lex(s) => write!(f, "LexError: {s}"),
             UnsupportedType(s) => write!(f, "TypeError: {s}"),
             UndefinedSymbol(s) => write!(f, "UndefinedError: '{s}' is undefined."),
             SymbolRedefinition(s) => write!(f, "StackError: Attempted to redefine symbol '{s}'."),
             Unimplemented(s) => write!(f, "ImplementationError: '{s}' is unimplemented."),
             Runtime(s) => write!(f, "RuntimeError: {s}"),
+            StackEffect(s) => write!(f, "StackEffectError: {s}"),
+            Io(s) => write!(f, "IOError: {s}"),
             Warning(s) => write!(f, "Warning: {s}"),
         }
     }
@@ -214,6 +237,12 @@ impl From<ParseFloatError> for NightError {
     }
 }
 
+impl From<std::io::Error> for NightError {
+    fn from(e: std::io::Error) -> Self {
+        NightError::Io(format!("{e}."))
+    }
+}
+
 impl<T> From<NightError> for Status<T>
 where
     T: Clone,
@@ -0,0 +1,220 @@
+use crate::utils;
+use crate::utils::error::{night_err, Status};
+use crate::value::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+/// The options following the `:` in a placeholder, e.g. `{:>8.2}`
+#[derive(Clone, Debug)]
+struct Spec {
+    fill: char,
+    align: Option<Align>,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    // Kept so a template can be rebuilt without its register names
+    raw: String,
+}
+
+#[derive(Clone, Debug)]
+enum Piece {
+    Lit(String),
+    Hole(Option<String>, Spec),
+}
+
+fn parse_spec(raw: &str) -> Status<Spec> {
+    let mut spec = Spec {
+        fill: ' ',
+        align: None,
+        zero: false,
+        width: 0,
+        precision: None,
+        raw: raw.to_string(),
+    };
+
+    let align = |c| match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    };
+
+    let chars = raw.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    if chars.len() > 1 && align(chars[1]).is_some() {
+        spec.fill = chars[0];
+        spec.align = align(chars[1]);
+        i = 2;
+    } else if let Some(a) = chars.first().copied().and_then(align) {
+        spec.align = Some(a);
+        i = 1;
+    }
+
+    if chars.get(i) == Some(&'0') {
+        spec.zero = true;
+        i += 1;
+    }
+
+    let rest = chars[i..].iter().collect::<String>();
+    let (width, precision) = match rest.split_once('.') {
+        Some((w, p)) => (w, Some(p)),
+        None => (rest.as_str(), None),
+    };
+
+    if !width.is_empty() {
+        spec.width = width.parse().or(night_err!(
+            Syntax,
+            format!("Invalid width '{width}' in format spec '{raw}'.")
+        ))?;
+    }
+    if let Some(p) = precision {
+        spec.precision = Some(p.parse().or(night_err!(
+            Syntax,
+            format!("Invalid precision '{p}' in format spec '{raw}'.")
+        ))?);
+    }
+
+    Ok(spec)
+}
+
+fn parse(template: &str) -> Status<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    let mut lit = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => lit.push('{'),
+            '}' if chars.next_if_eq(&'}').is_some() => lit.push('}'),
+            '{' => {
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => inner.push(c),
+                        None => return night_err!(Syntax, "Unclosed '{' in format string."),
+                    }
+                }
+
+                let (name, spec) = inner.split_once(':').unwrap_or((&inner, ""));
+                let name = match name.strip_prefix('$') {
+                    Some(reg) if utils::is_one_word(reg) && !reg.is_empty() => {
+                        Some(reg.to_string())
+                    }
+                    None if name.is_empty() => None,
+                    _ => {
+                        return night_err!(
+                            Syntax,
+                            format!("Invalid placeholder '{{{inner}}}' in format string.")
+                        )
+                    }
+                };
+
+                pieces.push(Piece::Lit(std::mem::take(&mut lit)));
+                pieces.push(Piece::Hole(name, parse_spec(spec)?));
+            }
+            '}' => return night_err!(Syntax, "Unmatched '}' in format string, use '}}'."),
+            c => lit.push(c),
+        }
+    }
+
+    pieces.push(Piece::Lit(lit));
+    Ok(pieces)
+}
+
+fn pad(s: String, spec: &Spec) -> String {
+    let len = s.chars().count();
+    if len >= spec.width {
+        return s;
+    }
+    let diff = spec.width - len;
+
+    // Zero padding goes between the sign and the digits
+    if spec.zero && spec.align.is_none() {
+        let (sign, digits) = match s.strip_prefix('-') {
+            Some(d) => ("-", d),
+            None => ("", s.as_str()),
+        };
+        return format!("{sign}{}{digits}", "0".repeat(diff));
+    }
+
+    let fill = |n| spec.fill.to_string().repeat(n);
+    match spec.align.unwrap_or(Align::Left) {
+        Align::Left => format!("{s}{}", fill(diff)),
+        Align::Right => format!("{}{s}", fill(diff)),
+        Align::Center => format!("{}{s}{}", fill(diff / 2), fill(diff - diff / 2)),
+    }
+}
+
+/// Number of values `format` needs to pop for this template
+pub fn arg_count(template: &str) -> Status<usize> {
+    let pieces = parse(template)?;
+    let mut count = 0;
+    for piece in pieces {
+        match piece {
+            Piece::Hole(None, _) => count += 1,
+            Piece::Hole(Some(reg), _) => {
+                return night_err!(
+                    Syntax,
+                    format!("Register '${reg}' can only be referenced in a string literal.")
+                )
+            }
+            Piece::Lit(_) => {}
+        }
+    }
+    Ok(count)
+}
+
+/// Fills the placeholders of `template` with `args`, in order
+pub fn format(template: &str, args: Vec<Value>) -> Status<String> {
+    let mut args = args.into_iter();
+    let mut buf = String::new();
+    for piece in parse(template)? {
+        match piece {
+            Piece::Lit(s) => buf.push_str(&s),
+            Piece::Hole(_, spec) => {
+                let Some(arg) = args.next() else {
+                    return night_err!(Runtime, "Not enough arguments for format string.");
+                };
+                let s = match spec.precision {
+                    Some(p) => format!("{arg:.p$}"),
+                    None => arg.to_string(),
+                };
+                buf.push_str(&pad(s, &spec));
+            }
+        }
+    }
+    Ok(buf)
+}
+
+/// Splits an interpolated literal like `"x: {$x:.2}"` into a plain template (`"x: {:.2}"`) and
+/// the registers it references, in order.
+pub fn interpolate(template: &str) -> Status<(String, Vec<String>)> {
+    let mut buf = String::new();
+    let mut regs = Vec::new();
+    for piece in parse(template)? {
+        match piece {
+            Piece::Lit(s) => buf.push_str(&s.replace('{', "{{").replace('}', "}}")),
+            Piece::Hole(Some(reg), spec) => {
+                regs.push(reg);
+                if spec.raw.is_empty() {
+                    buf.push_str("{}");
+                } else {
+                    buf.push_str(&format!("{{:{}}}", spec.raw));
+                }
+            }
+            Piece::Hole(None, _) => {
+                return night_err!(
+                    Syntax,
+                    "Interpolated strings can only reference registers, e.g. '{$x}'."
+                )
+            }
+        }
+    }
+    Ok((buf, regs))
+}
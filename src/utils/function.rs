@@ -1,7 +1,8 @@
 use std::rc::Rc;
 
+use crate::builtin::Intrinsic;
 use crate::interpreter::Instr;
-use crate::scope::{Scope, StackVal};
+use crate::scope::{Scope, ScopeInternal, StackVal};
 use crate::utils::error::Status;
 use crate::value::Value;
 
@@ -10,6 +11,95 @@ pub trait Generable {
     fn gen_instrs(&self, span: usize) -> Vec<Instr>;
 
     fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Renders the function back into Night syntax, e.g. `{ 1 + }`
+    fn source(&self) -> String {
+        fmt_block(&self.gen_instrs(usize::MAX))
+    }
+
+    /// The instruction a singleton function (`x@`) wraps, which is treated as a word when quoting
+    fn as_word(&self) -> Option<Instr> {
+        None
+    }
+}
+
+/// The instructions of a function that correspond to source-level words, i.e. without guards
+pub fn words(f: &dyn Generable) -> Vec<Instr> {
+    f.gen_instrs(usize::MAX)
+        .into_iter()
+        .filter(|i| {
+            !matches!(
+                i,
+                Instr::Guard(..) | Instr::GuardEnd(..) | Instr::Unblock(..)
+            )
+        })
+        .collect()
+}
+
+/// The elements of a function as they would be quoted: literals are themselves, nested functions
+/// are pushed as-is, and any other word becomes a singleton function
+pub fn elements(f: &dyn Generable) -> Vec<StackVal> {
+    words(f)
+        .into_iter()
+        .map(|instr| match instr {
+            Instr::Push(v, _) => StackVal::Value(v),
+            Instr::PushFunc(f, _) => StackVal::Function(f),
+            instr => StackVal::from(SingleFunc::from(instr)),
+        })
+        .collect()
+}
+
+/// Builds a function from elements, the inverse of `elements`
+pub fn quote(items: Vec<StackVal>) -> Rc<dyn Generable> {
+    let mut quot: Rc<dyn Generable> = Rc::new(BlockFunc::from(vec![]));
+    for item in items {
+        quot = Rc::new(ComposedFunc::new(quot, quote_one(item)));
+    }
+    quot
+}
+
+/// A function pushing `item`, or the word itself if `item` is a singleton function
+pub fn quote_one(item: StackVal) -> Rc<dyn Generable> {
+    match item {
+        StackVal::Function(f) if f.as_word().is_some() => f,
+        item => Rc::new(CurriedFunc::new(item, Rc::new(BlockFunc::from(vec![])))),
+    }
+}
+
+/// Structural equality, two functions are equal if they generate the same instructions
+pub fn fn_eq(left: &dyn Generable, right: &dyn Generable) -> bool {
+    left.gen_instrs(usize::MAX) == right.gen_instrs(usize::MAX)
+}
+
+fn fmt_block(instrs: &[Instr]) -> String {
+    // A guard spanning the whole function is written as `(a b) { ... }`
+    if let [Instr::Guard(guard, _), inner @ .., Instr::GuardEnd(_, _)] = instrs {
+        return format!("({}) {}", guard.join(" "), fmt_block(inner));
+    }
+
+    let mut words = Vec::with_capacity(instrs.len());
+    let mut iter = instrs.iter();
+    while let Some(instr) = iter.next() {
+        match instr {
+            Instr::Intrinsic(Intrinsic::DefineRegister, _) => {
+                if let Some(Instr::PushSym(reg, true, _)) = iter.next() {
+                    words.push(format!("${reg}!"));
+                }
+            }
+            Instr::Unblock(..) => {}
+            _ => words.push(instr.to_string()),
+        }
+    }
+
+    if words.is_empty() {
+        "{ }".to_string()
+    } else {
+        format!("{{ {} }}", words.join(" "))
+    }
 }
 
 #[derive(Clone)]
@@ -92,6 +182,14 @@ impl Generable for SingleFunc {
     fn len(&self) -> usize {
         1
     }
+
+    fn source(&self) -> String {
+        format!("{}@", self.0)
+    }
+
+    fn as_word(&self) -> Option<Instr> {
+        Some(self.0.clone())
+    }
 }
 
 impl<T> From<T> for BlockFunc
@@ -127,6 +225,25 @@ impl From<Instr> for SingleFunc {
     }
 }
 
+/// Something an arity helper can pop off the stack as an argument
+pub trait FromStack: Sized {
+    fn pop_from(scope: &mut ScopeInternal) -> Status<Self>;
+}
+
+impl FromStack for Value {
+    #[inline]
+    fn pop_from(scope: &mut ScopeInternal) -> Status<Self> {
+        scope.pop_value()
+    }
+}
+
+impl FromStack for StackVal {
+    #[inline]
+    fn pop_from(scope: &mut ScopeInternal) -> Status<Self> {
+        scope.pop()
+    }
+}
+
 #[inline]
 pub fn arity0_1<T>(def: fn(Scope) -> Status<T>, scope: Scope) -> Status
 where
@@ -138,34 +255,55 @@ where
 }
 
 #[inline]
-pub fn arity1_0(def: fn(Scope, Value) -> Status, scope: Scope) -> Status {
+pub fn arity1_0<A: FromStack>(def: fn(Scope, A) -> Status, scope: Scope) -> Status {
     let mut s = scope.borrow_mut();
-    let arg = s.pop_value()?;
+    let arg = A::pop_from(&mut s)?;
     drop(s);
     def(scope, arg)
 }
 
 #[inline]
-pub fn arity1_1<T>(def: fn(Scope, Value) -> Status<T>, scope: Scope) -> Status
+pub fn arity1_1<A: FromStack, T>(def: fn(Scope, A) -> Status<T>, scope: Scope) -> Status
 where
     T: Into<StackVal>,
 {
-    let arg = scope.borrow_mut().pop_value()?;
+    let arg = A::pop_from(&mut scope.borrow_mut())?;
     let v = def(scope.clone(), arg)?.into();
     scope.borrow_mut().push(v);
     Ok(())
 }
 
 #[inline]
-pub fn arity2_1<T>(def: fn(Scope, Value, Value) -> Status<T>, scope: Scope) -> Status
+pub fn arity2_1<A: FromStack, B: FromStack, T>(
+    def: fn(Scope, A, B) -> Status<T>,
+    scope: Scope,
+) -> Status
 where
     T: Into<StackVal>,
 {
     let mut s = scope.borrow_mut();
-    let right = s.pop_value()?;
-    let left = s.pop_value()?;
+    let right = B::pop_from(&mut s)?;
+    let left = A::pop_from(&mut s)?;
     drop(s);
     let v = def(scope.clone(), left, right)?.into();
     scope.borrow_mut().push(v);
     Ok(())
 }
+
+#[inline]
+pub fn arity3_1<A: FromStack, B: FromStack, C: FromStack, T>(
+    def: fn(Scope, A, B, C) -> Status<T>,
+    scope: Scope,
+) -> Status
+where
+    T: Into<StackVal>,
+{
+    let mut s = scope.borrow_mut();
+    let c = C::pop_from(&mut s)?;
+    let b = B::pop_from(&mut s)?;
+    let a = A::pop_from(&mut s)?;
+    drop(s);
+    let v = def(scope.clone(), a, b, c)?.into();
+    scope.borrow_mut().push(v);
+    Ok(())
+}
@@ -0,0 +1,345 @@
+use crate::utils::error::{night_err, Status};
+use crate::value::{Map, Value};
+
+// Arrays and objects are parsed recursively, so deeper documents are rejected before they can
+// overflow the stack
+const MAX_DEPTH: usize = 512;
+
+/// Parses a JSON document. Objects become maps, integers without a fraction or exponent become
+/// `i64`s, other numbers `f64`s, and `null` becomes `nil`.
+pub fn parse(src: &str) -> Status<Value> {
+    let mut parser = Parser {
+        src,
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_ws();
+    let value = parser.value()?;
+    parser.skip_ws();
+    if parser.pos < src.len() {
+        return parser.fail("expected the end of input");
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    // Byte offset into `src`
+    pos: usize,
+    // Number of arrays and objects currently open
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn fail<T>(&self, msg: &str) -> Status<T> {
+        night_err!(
+            Runtime,
+            format!("Invalid JSON at byte {}: {msg}.", self.pos)
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.src.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, lit: &str) -> Status {
+        if self.src[self.pos..].starts_with(lit) {
+            self.pos += lit.len();
+            Ok(())
+        } else {
+            self.fail(&format!("expected '{lit}'"))
+        }
+    }
+
+    fn value(&mut self) -> Status<Value> {
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::from(self.string()?)),
+            Some(b't') => self.expect("true").map(|_| Value::from(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::from(false)),
+            Some(b'n') => self.expect("null").map(|_| Value::nil()),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => self.fail("expected a value"),
+            None => self.fail("unexpected end of input"),
+        }
+    }
+
+    // Shared by arrays and objects, `item` parses one element and its surrounding whitespace
+    fn list(&mut self, close: u8, mut item: impl FnMut(&mut Self) -> Status) -> Status {
+        if self.depth == MAX_DEPTH {
+            return self.fail("nesting too deep");
+        }
+        self.depth += 1;
+        self.pos += 1;
+        self.skip_ws();
+        if self.peek() == Some(close) {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(());
+        }
+
+        loop {
+            item(self)?;
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(());
+                }
+                _ => return self.fail(&format!("expected ',' or '{}'", close as char)),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Status<Value> {
+        let mut items = Vec::new();
+        self.list(b']', |p| {
+            p.skip_ws();
+            items.push(p.value()?);
+            p.skip_ws();
+            Ok(())
+        })?;
+        Ok(Value::from(items))
+    }
+
+    fn object(&mut self) -> Status<Value> {
+        let mut map = Map::new();
+        self.list(b'}', |p| {
+            p.skip_ws();
+            if p.peek() != Some(b'"') {
+                return p.fail("expected a string key");
+            }
+            let key = p.string()?;
+            p.skip_ws();
+            p.expect(":")?;
+            p.skip_ws();
+            map.insert(Value::from(key), p.value()?);
+            p.skip_ws();
+            Ok(())
+        })?;
+        Ok(Value::from(map))
+    }
+
+    fn number(&mut self) -> Status<Value> {
+        let start = self.pos;
+        let digits = |p: &mut Self| {
+            let from = p.pos;
+            while matches!(p.peek(), Some(b'0'..=b'9')) {
+                p.pos += 1;
+            }
+            p.pos > from
+        };
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return self.fail("expected a digit");
+        }
+
+        let mut is_float = false;
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            is_float = true;
+            if !digits(self) {
+                return self.fail("expected a digit after '.'");
+            }
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            is_float = true;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return self.fail("expected a digit in the exponent");
+            }
+        }
+
+        let lit = &self.src[start..self.pos];
+        // Integers too large for an `i64` are kept as floats
+        match lit.parse::<i64>() {
+            Ok(n) if !is_float => Ok(Value::from(n)),
+            _ => Ok(Value::from(lit.parse::<f64>()?)),
+        }
+    }
+
+    fn hex4(&mut self) -> Status<u32> {
+        let hex = self.src.get(self.pos..self.pos + 4).unwrap_or("");
+        match u32::from_str_radix(hex, 16) {
+            Ok(n) if hex.len() == 4 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                self.pos += 4;
+                Ok(n)
+            }
+            _ => self.fail("expected 4 hex digits after '\\u'"),
+        }
+    }
+
+    fn string(&mut self) -> Status<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let Some(c) = self.src[self.pos..].chars().next() else {
+                return self.fail("unterminated string");
+            };
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    return Ok(out);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    let escaped = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.pos += 1;
+                            out.push(self.unicode_escape()?);
+                            continue;
+                        }
+                        _ => return self.fail("unknown escape sequence"),
+                    };
+                    self.pos += 1;
+                    out.push(escaped);
+                }
+                c if (c as u32) < 0x20 => return self.fail("control character in string"),
+                c => {
+                    self.pos += c.len_utf8();
+                    out.push(c);
+                }
+            }
+        }
+    }
+
+    // Characters outside the BMP are written as a surrogate pair, e.g. `\ud83c\udf19`
+    fn unicode_escape(&mut self) -> Status<char> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            self.expect("\\u")?;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return self.fail("expected a low surrogate");
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.fail("invalid unicode escape"),
+        }
+    }
+}
+
+/// Quotes and escapes `s` as a JSON string
+pub fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn err(src: &str) -> String {
+        parse(src).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        let src = r#" { "null": null, "bools": [true, false], "int": -42, "float": 1.5,
+            "exp": 1e3, "esc": "a\"b\\c\n\t\u0001", "uni": "é🌙",
+            "nested": [[], {}, [{"k": [1, [2]]}]] } "#;
+        let json = r#"{"null":null,"bools":[true,false],"int":-42,"float":1.5,"exp":1000.0,"esc":"a\"b\\c\n\t\u0001","uni":"é🌙","nested":[[],{},[{"k":[1,[2]]}]]}"#;
+
+        let value = parse(src).unwrap();
+        assert_eq!(value.to_json().unwrap(), json);
+        assert_eq!(parse(json).unwrap(), value);
+    }
+
+    #[test]
+    fn ints_and_floats() {
+        for src in ["0", "-7", "9223372036854775807"] {
+            assert!(parse(src).unwrap().is_int(), "{src}");
+        }
+        // Too large for an `i64`
+        for src in ["1.0", "-0.5", "2e2", "1E-2", "9223372036854775808"] {
+            assert!(parse(src).unwrap().is_float(), "{src}");
+        }
+        assert_eq!(parse("[1, 1.0]").unwrap().to_json().unwrap(), "[1,1.0]");
+    }
+
+    #[test]
+    fn error_offsets() {
+        assert_eq!(
+            err(""),
+            "RuntimeError: Invalid JSON at byte 0: unexpected end of input."
+        );
+        assert_eq!(
+            err("[1 2]"),
+            "RuntimeError: Invalid JSON at byte 3: expected ',' or ']'."
+        );
+        assert_eq!(
+            err(r#"{"a" 1}"#),
+            "RuntimeError: Invalid JSON at byte 5: expected ':'."
+        );
+        assert_eq!(
+            err("\"é\\x\""),
+            "RuntimeError: Invalid JSON at byte 4: unknown escape sequence."
+        );
+        assert_eq!(
+            err("1 2"),
+            "RuntimeError: Invalid JSON at byte 2: expected the end of input."
+        );
+        assert_eq!(
+            err("01"),
+            "RuntimeError: Invalid JSON at byte 1: expected the end of input."
+        );
+        assert_eq!(
+            err("\"abc"),
+            "RuntimeError: Invalid JSON at byte 4: unterminated string."
+        );
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let deep = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+        assert!(parse(&deep).is_ok());
+
+        let too_deep = "[".repeat(200_000);
+        assert_eq!(
+            err(&too_deep),
+            format!("RuntimeError: Invalid JSON at byte {MAX_DEPTH}: nesting too deep.")
+        );
+    }
+}
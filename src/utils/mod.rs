@@ -1,20 +1,48 @@
+use crate::utils::error::{NightError, Status};
+use crate::utils::output::Reader;
 use crate::{interpreter::Night, lexer::Lexer};
 
+pub mod csv;
 pub mod error;
+pub mod format;
 pub mod function;
+pub mod json;
+pub mod output;
+pub mod pattern;
+pub mod process;
+pub mod time;
 
 #[inline]
 pub fn valid_symbol_chr(c: char) -> bool {
     c == '_' || c.is_ascii_alphanumeric()
 }
 
+/// Words may also start with `>` (`>quot`), end with `?` (`some?`) or `>` (`quot>`), and be
+/// joined with `.` (`point.x`)
 pub fn is_one_word(s: &str) -> bool {
-    s.chars().all(valid_symbol_chr)
+    let s = s.strip_prefix('>').unwrap_or(s);
+    let s = s.strip_suffix(['?', '>']).unwrap_or(s);
+    s.split('.')
+        .all(|part| !part.is_empty() && part.chars().all(valid_symbol_chr))
 }
 
-pub fn define_fns(night: &mut Night, def: &'static str) {
-    let lexer = Lexer::new(def);
-    let tokens = lexer.tokenize();
-    night.push_new_code(def, tokens);
-    night.exec();
+/// Reads the next line of `input` without its line ending, or `None` at the end of input
+pub fn read_input_line(input: &Reader) -> Status<Option<String>> {
+    let mut line = String::new();
+    match input.borrow_mut().read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => {
+            let len = line.trim_end_matches(['\n', '\r']).len();
+            line.truncate(len);
+            Ok(Some(line))
+        }
+        Err(e) => Err(NightError::Io(format!("Could not read input: {e}."))),
+    }
+}
+
+pub fn define_fns(night: &mut Night, def: &'static str) -> Status {
+    let lexer = Lexer::new(def).with_errors(night.out());
+    let tokens = lexer.tokenize()?;
+    night.push_new_code(def, tokens)?;
+    night.exec()
 }
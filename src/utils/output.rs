@@ -0,0 +1,51 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+
+/// A shared destination for text, so an embedder can capture what a program prints
+pub type Writer = Rc<RefCell<dyn Write>>;
+
+/// A shared source of text for `read_line` and friends, so an embedder can feed a program input
+pub type Reader = Rc<RefCell<dyn Input>>;
+
+/// What `Reader` needs, implemented for any `BufRead`, like a `Cursor` over a string
+pub trait Input {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize>;
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize>;
+}
+
+impl<T: BufRead> Input for T {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        BufRead::read_line(self, buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        io::Read::read_to_string(self, buf)
+    }
+}
+
+// Locks stdin only for each read, so creating a `Night` never blocks another one, or another
+// thread, from reading it
+struct Stdin;
+
+impl Input for Stdin {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        io::stdin().read_line(buf)
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        io::Read::read_to_string(&mut io::stdin(), buf)
+    }
+}
+
+pub fn stdout() -> Writer {
+    Rc::new(RefCell::new(io::stdout()))
+}
+
+pub fn stderr() -> Writer {
+    Rc::new(RefCell::new(io::stderr()))
+}
+
+pub fn stdin() -> Reader {
+    Rc::new(RefCell::new(Stdin))
+}
@@ -0,0 +1,144 @@
+use std::rc::Rc;
+
+use crate::interpreter::Instr;
+use crate::scope::StackVal;
+use crate::utils::error::{night_err, Status};
+use crate::utils::function::Generable;
+use crate::value::Value;
+
+/// A pattern in a `case` clause, e.g. `1`, `$x`, `[ 0 $y ]` or `#{ :name $n }`
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    Lit(Value),
+    Bind(String),
+    Array(Vec<Pattern>),
+    // Matches maps containing every key, or structs with every field
+    Map(Vec<(Value, Pattern)>),
+}
+
+pub struct Clauses {
+    pub arms: Vec<(Pattern, Rc<dyn Generable>)>,
+    pub default: Option<Rc<dyn Generable>>,
+}
+
+impl Pattern {
+    /// Registers bound by the pattern are appended to `binds`, which is only meaningful on a match
+    pub fn matches(&self, value: &StackVal, binds: &mut Vec<(String, StackVal)>) -> bool {
+        match self {
+            Pattern::Lit(lit) => matches!(value, StackVal::Value(v) if v == lit),
+            Pattern::Bind(reg) => {
+                binds.push((reg.clone(), value.clone()));
+                true
+            }
+            Pattern::Array(items) => match value {
+                StackVal::Value(v) if v.is_array() => {
+                    let values = v.clone().as_array().unwrap();
+                    values.len() == items.len()
+                        && items
+                            .iter()
+                            .zip(values)
+                            .all(|(p, v)| p.matches(&StackVal::Value(v), binds))
+                }
+                _ => false,
+            },
+            Pattern::Map(entries) => match value {
+                StackVal::Value(v) if v.is_map() => {
+                    let map = v.clone().as_map().unwrap();
+                    entries.iter().all(|(k, p)| {
+                        map.get(k)
+                            .is_some_and(|v| p.matches(&StackVal::Value(v.clone()), binds))
+                    })
+                }
+                StackVal::Value(v) if v.is_struct() => {
+                    let s = v.clone().as_struct().unwrap();
+                    entries.iter().all(|(k, p)| {
+                        let field = k.clone().as_str().ok();
+                        field
+                            .and_then(|f| s.get(&f).ok().cloned())
+                            .is_some_and(|v| p.matches(&StackVal::Value(v), binds))
+                    })
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+fn parse_pattern(instrs: &[Instr], i: &mut usize) -> Status<Pattern> {
+    let instr = &instrs[*i];
+    *i += 1;
+    match instr {
+        Instr::Push(v, _) => Ok(Pattern::Lit(v.clone())),
+        Instr::PushSym(reg, true, _) => Ok(Pattern::Bind(reg.clone())),
+        Instr::ArrayStart(_) => {
+            let mut items = Vec::new();
+            while !matches!(instrs.get(*i), Some(Instr::ArrayEnd(_)) | None) {
+                items.push(parse_pattern(instrs, i)?);
+            }
+            if *i == instrs.len() {
+                return night_err!(Syntax, "Unbalanced array pattern.");
+            }
+            *i += 1;
+            Ok(Pattern::Array(items))
+        }
+        Instr::MapStart(_) => {
+            let mut entries = Vec::new();
+            loop {
+                match instrs.get(*i) {
+                    Some(Instr::MapEnd(_)) => break,
+                    Some(Instr::Push(k, _)) => {
+                        *i += 1;
+                        if matches!(instrs.get(*i), Some(Instr::MapEnd(_)) | None) {
+                            return night_err!(Syntax, "Map pattern is missing a value pattern.");
+                        }
+                        entries.push((k.clone(), parse_pattern(instrs, i)?));
+                    }
+                    Some(instr) => {
+                        return night_err!(
+                            Syntax,
+                            format!("Map pattern keys must be literals, got '{instr}'.")
+                        )
+                    }
+                    None => return night_err!(Syntax, "Unbalanced map pattern."),
+                }
+            }
+            *i += 1;
+            Ok(Pattern::Map(entries))
+        }
+        instr => night_err!(
+            Syntax,
+            format!(
+                "'{instr}' is not a valid pattern, expected a literal, register, array or map."
+            )
+        ),
+    }
+}
+
+/// Splits the words of a `case` quotation into `pattern { body }` arms, and an optional trailing
+/// `{ default }`
+pub fn parse_clauses(instrs: &[Instr]) -> Status<Clauses> {
+    let mut arms = Vec::new();
+    let mut i = 0;
+    while i < instrs.len() {
+        if let Instr::PushFunc(f, _) = &instrs[i] {
+            if i + 1 == instrs.len() {
+                return Ok(Clauses {
+                    arms,
+                    default: Some(f.clone()),
+                });
+            }
+        }
+
+        let pattern = parse_pattern(instrs, &mut i)?;
+        match instrs.get(i) {
+            Some(Instr::PushFunc(f, _)) => arms.push((pattern, f.clone())),
+            _ => return night_err!(Syntax, "Each case pattern must be followed by a block."),
+        }
+        i += 1;
+    }
+
+    Ok(Clauses {
+        arms,
+        default: None,
+    })
+}
@@ -0,0 +1,22 @@
+use std::collections::HashMap;
+use std::env;
+
+/// What a script can see of the process running it. `Night` starts with an empty one, so
+/// embedders decide what to expose, while the CLI passes on the real process.
+#[derive(Clone, Debug, Default)]
+pub struct Process {
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub script_path: Option<String>,
+}
+
+impl Process {
+    /// The current process's environment, with `args` being what follows the script path
+    pub fn from_env(script_path: Option<String>, args: Vec<String>) -> Self {
+        Self {
+            args,
+            env: env::vars().collect(),
+            script_path,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::utils::error::{night_err, Status};
+
+/// Seconds since the Unix epoch
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |d| d.as_secs_f64())
+}
+
+/// Nanoseconds since an unspecified point, only meaningful when compared to another reading
+pub fn monotonic_ns() -> i64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_nanos() as i64
+}
+
+// Howard Hinnant's `civil_from_days`, for the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Formats Unix seconds as UTC. Supports `%Y`, `%m`, `%d`, `%H`, `%M`, `%S` and `%%`.
+pub fn format(secs: i64, fmt: &str) -> Status<String> {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let time = secs.rem_euclid(86400);
+    let (hour, min, sec) = (time / 3600, time % 3600 / 60, time % 60);
+
+    let mut out = String::with_capacity(fmt.len());
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('Y') => out.push_str(&format!("{year:04}")),
+            Some('m') => out.push_str(&format!("{month:02}")),
+            Some('d') => out.push_str(&format!("{day:02}")),
+            Some('H') => out.push_str(&format!("{hour:02}")),
+            Some('M') => out.push_str(&format!("{min:02}")),
+            Some('S') => out.push_str(&format!("{sec:02}")),
+            Some('%') => out.push('%'),
+            Some(c) => return night_err!(Runtime, format!("Unknown time format '%{c}'.")),
+            None => return night_err!(Runtime, "Time format cannot end with '%'."),
+        }
+    }
+    Ok(out)
+}
+
+/// The inverse of `format`, returning `None` when `s` doesn't match `fmt`. Fields missing from
+/// `fmt` default to the start of the epoch.
+pub fn parse(s: &str, fmt: &str) -> Status<Option<i64>> {
+    let mut fields = [1970, 1, 1, 0, 0, 0];
+    let mut input = s;
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        let (field, width) = match c {
+            '%' => match chars.next() {
+                Some('Y') => (0, 4),
+                Some('m') => (1, 2),
+                Some('d') => (2, 2),
+                Some('H') => (3, 2),
+                Some('M') => (4, 2),
+                Some('S') => (5, 2),
+                Some('%') => match input.strip_prefix('%') {
+                    Some(rest) => {
+                        input = rest;
+                        continue;
+                    }
+                    None => return Ok(None),
+                },
+                Some(c) => return night_err!(Runtime, format!("Unknown time format '%{c}'.")),
+                None => return night_err!(Runtime, "Time format cannot end with '%'."),
+            },
+            c => match input.strip_prefix(c) {
+                Some(rest) => {
+                    input = rest;
+                    continue;
+                }
+                None => return Ok(None),
+            },
+        };
+
+        let digits = input
+            .char_indices()
+            .take(width)
+            .take_while(|(_, c)| c.is_ascii_digit())
+            .count();
+        match input[..digits].parse() {
+            Ok(n) => fields[field] = n,
+            Err(_) => return Ok(None),
+        }
+        input = &input[digits..];
+    }
+
+    let [year, month, day, hour, min, sec] = fields;
+    // Converting back catches dates like February 30th
+    let days = days_from_civil(year, month, day);
+    if !input.is_empty() || civil_from_days(days) != (year, month, day) {
+        return Ok(None);
+    }
+    if hour > 23 || min > 59 || sec > 60 {
+        return Ok(None);
+    }
+
+    Ok(Some(days * 86400 + hour * 3600 + min * 60 + sec))
+}
@@ -1,13 +1,133 @@
 use std::fmt::{self, Display};
 use std::ops::{Add, Div, Mul, Rem, Sub};
+use std::rc::Rc;
 
-use crate::utils::error::{night_err, Status};
+use crate::utils::error::{night_err, NightError, Status};
+use crate::utils::json;
 
 #[derive(Clone, Debug)]
 enum Type {
     Int(i64),
     Float(f64),
     Str(String),
+    Bool(bool),
+    Array(Vec<Value>),
+    Map(Map),
+    Struct(Struct),
+    Nil,
+}
+
+/// Insertion ordered map. Keys can be any value, so lookups compare with `PartialEq`.
+#[derive(Clone, Debug, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn position(&self, key: &Value) -> Option<usize> {
+        self.entries.iter().position(|(k, _)| k == key)
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.position(key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn contains(&self, key: &Value) -> bool {
+        self.position(key).is_some()
+    }
+
+    /// Overwrites the value of an existing key in place, otherwise appends the entry
+    pub fn insert(&mut self, key: Value, value: Value) {
+        match self.position(&key) {
+            Some(i) => self.entries[i].1 = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        self.position(key).map(|i| self.entries.remove(i).1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Value, Value)> {
+        self.entries.iter()
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (Value, Value);
+    type IntoIter = std::vec::IntoIter<(Value, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+// Maps are equal when they hold the same entries, regardless of order
+impl PartialEq for Map {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(k, v)| other.get(k).is_some_and(|o| o == v))
+    }
+}
+
+/// An instance of a type declared with `-> struct name (fields...)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Struct {
+    name: Rc<str>,
+    fields: Rc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Struct {
+    pub fn new(name: Rc<str>, fields: Rc<[String]>, values: Vec<Value>) -> Self {
+        Self {
+            name,
+            fields,
+            values,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn position(&self, field: &str) -> Status<usize> {
+        self.fields
+            .iter()
+            .position(|f| f == field)
+            .ok_or(NightError::Runtime(format!(
+                "'{}' has no field '{field}'.",
+                self.name
+            )))
+    }
+
+    pub fn get(&self, field: &str) -> Status<&Value> {
+        Ok(&self.values[self.position(field)?])
+    }
+
+    pub fn set(&mut self, field: &str, value: Value) -> Status {
+        let i = self.position(field)?;
+        self.values[i] = value;
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.fields.iter().zip(&self.values)
+    }
 }
 
 #[repr(transparent)]
@@ -22,31 +142,27 @@ impl Value {
         match &left.t {
             Type::Int(_) | Type::Float(_) => matches!(right.t, Type::Int(_) | Type::Float(_)),
             Type::Str(_) => matches!(right.t, Type::Str(_)),
+            Type::Bool(_) => matches!(right.t, Type::Bool(_)),
+            Type::Array(_) => matches!(right.t, Type::Array(_)),
+            Type::Map(_) => matches!(right.t, Type::Map(_)),
+            Type::Struct(l) => matches!(&right.t, Type::Struct(r) if l.name == r.name),
+            Type::Nil => matches!(right.t, Type::Nil),
         }
     }
 
     #[inline]
     pub fn is_num(&self) -> bool {
-        match self.t {
-            Type::Int(_) | Type::Float(_) => true,
-            _ => false,
-        }
+        matches!(self.t, Type::Int(_) | Type::Float(_))
     }
 
     #[inline]
     pub fn is_int(&self) -> bool {
-        match self.t {
-            Type::Int(_) => true,
-            _ => false,
-        }
+        matches!(self.t, Type::Int(_))
     }
 
     #[inline]
     pub fn is_float(&self) -> bool {
-        match self.t {
-            Type::Float(_) => true,
-            _ => false,
-        }
+        matches!(self.t, Type::Float(_))
     }
 
     #[inline]
@@ -58,6 +174,18 @@ impl Value {
         }
     }
 
+    /// Unlike `as_int`, this refuses to truncate floats, as bitwise operations on them are meaningless
+    #[inline]
+    pub fn as_bits(self) -> Status<i64> {
+        match self.t {
+            Type::Int(n) => Ok(n),
+            _ => night_err!(
+                UnsupportedType,
+                format!("Bitwise operations require an integer, got '{self}'.")
+            ),
+        }
+    }
+
     #[inline]
     pub fn as_float(self) -> Status<f64> {
         match self.t {
@@ -69,10 +197,7 @@ impl Value {
 
     #[inline]
     pub fn is_str(&self) -> bool {
-        match self.t {
-            Type::Str(_) => true,
-            _ => false,
-        }
+        matches!(self.t, Type::Str(_))
     }
 
     #[inline]
@@ -83,26 +208,215 @@ impl Value {
         }
     }
 
+    #[inline]
+    pub fn is_array(&self) -> bool {
+        matches!(self.t, Type::Array(_))
+    }
+
+    #[inline]
+    pub fn as_array(self) -> Status<Vec<Value>> {
+        match self.t {
+            Type::Array(a) => Ok(a),
+            _ => night_err!(UnsupportedType, "Expected array."),
+        }
+    }
+
+    #[inline]
+    pub fn is_map(&self) -> bool {
+        matches!(self.t, Type::Map(_))
+    }
+
+    #[inline]
+    pub fn as_map(self) -> Status<Map> {
+        match self.t {
+            Type::Map(m) => Ok(m),
+            _ => night_err!(UnsupportedType, "Expected map."),
+        }
+    }
+
+    #[inline]
+    pub fn is_struct(&self) -> bool {
+        matches!(self.t, Type::Struct(_))
+    }
+
+    #[inline]
+    pub fn as_struct(self) -> Status<Struct> {
+        match self.t {
+            Type::Struct(s) => Ok(s),
+            _ => night_err!(UnsupportedType, "Expected struct."),
+        }
+    }
+
+    /// Renders the value as a Night literal, e.g. strings are quoted and escaped
+    pub fn repr(&self) -> String {
+        match &self.t {
+            Type::Str(s) => format!("{s:?}"),
+            Type::Array(a) => {
+                let items = a.iter().map(Value::repr).collect::<Vec<_>>();
+                format!("[{}]", items.join(" "))
+            }
+            Type::Map(m) => {
+                let items = m
+                    .iter()
+                    .map(|(k, v)| format!("{} {}", k.repr(), v.repr()))
+                    .collect::<Vec<_>>();
+                format!("#{{{}}}", items.join(" "))
+            }
+            Type::Struct(s) => {
+                let fields = s
+                    .iter()
+                    .map(|(k, v)| format!("{k}: {}", v.repr()))
+                    .collect::<Vec<_>>();
+                format!("{}({})", s.name, fields.join(", "))
+            }
+            _ => self.to_string(),
+        }
+    }
+
+    /// Renders the value as JSON. Structs become objects of their fields, while maps need string
+    /// keys and floats need to be finite.
+    pub fn to_json(&self) -> Status<String> {
+        Ok(match &self.t {
+            Type::Int(n) => n.to_string(),
+            Type::Float(n) if n.is_finite() => format!("{n:?}"),
+            Type::Float(n) => {
+                return night_err!(UnsupportedType, format!("Cannot write {n} as JSON."))
+            }
+            Type::Str(s) => json::escape(s),
+            Type::Bool(b) => b.to_string(),
+            Type::Array(a) => {
+                let items = a.iter().map(Value::to_json).collect::<Status<Vec<_>>>()?;
+                format!("[{}]", items.join(","))
+            }
+            Type::Map(m) => {
+                let entries = m
+                    .iter()
+                    .map(|(k, v)| match &k.t {
+                        Type::Str(k) => Ok(format!("{}:{}", json::escape(k), v.to_json()?)),
+                        _ => night_err!(
+                            UnsupportedType,
+                            format!("JSON object keys must be strings, got '{}'.", k.repr())
+                        ),
+                    })
+                    .collect::<Status<Vec<_>>>()?;
+                format!("{{{}}}", entries.join(","))
+            }
+            Type::Struct(s) => {
+                let fields = s
+                    .iter()
+                    .map(|(k, v)| Ok(format!("{}:{}", json::escape(k), v.to_json()?)))
+                    .collect::<Status<Vec<_>>>()?;
+                format!("{{{}}}", fields.join(","))
+            }
+            Type::Nil => "null".to_string(),
+        })
+    }
+
+    #[inline]
+    pub fn nil() -> Self {
+        Self { t: Type::Nil }
+    }
+
+    #[inline]
+    pub fn is_nil(&self) -> bool {
+        matches!(self.t, Type::Nil)
+    }
+
+    #[inline]
+    pub fn is_bool(&self) -> bool {
+        matches!(self.t, Type::Bool(_))
+    }
+
+    /// Truthiness used by `if` and the logical builtins: booleans are themselves, numbers are
+    /// truthy when nonzero, strings and collections are truthy when nonempty, and `nil` is falsy.
     #[inline]
     pub fn as_bool(self) -> Status<bool> {
         match self.t {
-            Type::Int(n) if n == 0 => Ok(false),
-            Type::Int(n) if n > 0 => Ok(true),
-            Type::Int(_) => night_err!(
-                UnsupportedType,
-                "To coerce an integer into a boolean, it must be positive."
-            ),
-            Type::Float(n) if n == 0. => Ok(false),
-            Type::Float(n) if n > 0. => Ok(true),
-            Type::Float(_) => night_err!(
-                UnsupportedType,
-                "To coerce a float into a boolean, it must be positive."
-            ),
-            _ => night_err!(NaN),
+            Type::Bool(b) => Ok(b),
+            Type::Int(n) => Ok(n != 0),
+            Type::Float(n) => Ok(n != 0.),
+            Type::Str(s) => Ok(!s.is_empty()),
+            Type::Array(a) => Ok(!a.is_empty()),
+            Type::Map(m) => Ok(!m.is_empty()),
+            Type::Struct(_) => Ok(true),
+            Type::Nil => Ok(false),
         }
     }
 }
 
+/// Parses a number literal as accepted by the lexer, e.g. `-1_000`, `0xFF`, `1.5e3`, `3f64`
+pub fn parse_number(lit: &str) -> Status<Value> {
+    let clean = lit.replace('_', "");
+    let (neg, body) = match clean.strip_prefix('-') {
+        Some(body) => (true, body),
+        None => (false, clean.as_str()),
+    };
+
+    let radix = match body.get(..2) {
+        Some("0x") => Some((16, "hexadecimal")),
+        Some("0b") => Some((2, "binary")),
+        Some("0o") => Some((8, "octal")),
+        _ => None,
+    };
+    if let Some((radix, name)) = radix {
+        let digits = body[2..].strip_suffix("i64").unwrap_or(&body[2..]);
+        if digits.is_empty() {
+            return night_err!(Syntax, format!("Missing digits in {name} literal '{lit}'."));
+        }
+        if let Some(c) = digits.chars().find(|c| !c.is_digit(radix)) {
+            return night_err!(
+                Syntax,
+                format!("Invalid digit '{c}' in {name} literal '{lit}'.")
+            );
+        }
+        let n = i128::from_str_radix(digits, radix).unwrap_or(i128::MAX);
+        let n = if neg { -n } else { n };
+        return i64::try_from(n).map(Value::from).or(night_err!(
+            Syntax,
+            format!("Integer literal '{lit}' is out of range for i64.")
+        ));
+    }
+
+    let (digits, suffix) =
+        match body.find(|c: char| c.is_ascii_alphabetic() && c != 'e' && c != 'E') {
+            Some(i) => body.split_at(i),
+            None => (body, ""),
+        };
+    let is_float = digits.contains(['.', 'e', 'E']);
+    match suffix {
+        "" if is_float => {}
+        "" | "i64" if !is_float => {
+            let n = format!("{}{digits}", if neg { "-" } else { "" });
+            return n.parse::<i64>().map(Value::from).or(night_err!(
+                Syntax,
+                format!("Integer literal '{lit}' is out of range for i64.")
+            ));
+        }
+        "f64" => {}
+        "i64" => {
+            return night_err!(
+                Syntax,
+                format!("Float literal '{lit}' cannot have an 'i64' suffix.")
+            )
+        }
+        _ => {
+            return night_err!(
+                Syntax,
+                format!("Invalid suffix '{suffix}' on number literal '{lit}'.")
+            )
+        }
+    }
+
+    match format!("{}{digits}", if neg { "-" } else { "" }).parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(Value::from(n)),
+        Ok(_) => night_err!(
+            Syntax,
+            format!("Float literal '{lit}' is out of range for f64.")
+        ),
+        Err(_) => night_err!(Syntax, format!("Invalid number literal '{lit}'.")),
+    }
+}
+
 // Macro to quickly impl the various arithmetic operations for `Value`
 macro_rules! impl_arith_ops {
     ($($name:ident, $f:ident, $lit:literal, [$a1:ident, $a2:ident] $operation:block);*;) => {
@@ -111,6 +425,13 @@ macro_rules! impl_arith_ops {
                 type Output = Status<Value>;
 
                 fn $f(self, rhs: Self) -> Self::Output {
+                    if !self.is_num() || !rhs.is_num() {
+                        return night_err!(
+                            UnsupportedType,
+                            format!("Cannot {} '{self}' and '{rhs}'.", $lit)
+                        );
+                    }
+
                     if self.is_float() || rhs.is_float() {
                         let $a1 = self.as_float()?;
                         let $a2 = rhs.as_float()?;
@@ -151,6 +472,23 @@ impl PartialEq for Value {
                 Type::Str(right) => left == right,
                 _ => false,
             },
+            Type::Bool(left) => match &other.t {
+                Type::Bool(right) => left == right,
+                _ => false,
+            },
+            Type::Array(left) => match &other.t {
+                Type::Array(right) => left == right,
+                _ => false,
+            },
+            Type::Map(left) => match &other.t {
+                Type::Map(right) => left == right,
+                _ => false,
+            },
+            Type::Struct(left) => match &other.t {
+                Type::Struct(right) => left == right,
+                _ => false,
+            },
+            Type::Nil => other.is_nil(),
         }
     }
 }
@@ -169,9 +507,19 @@ impl PartialOrd for Value {
                 _ => None,
             },
             Type::Str(left) => match &other.t {
-                Type::Str(right) => left.partial_cmp(&right),
+                Type::Str(right) => left.partial_cmp(right),
+                _ => None,
+            },
+            Type::Bool(left) => match &other.t {
+                Type::Bool(right) => left.partial_cmp(right),
                 _ => None,
             },
+            Type::Array(left) => match &other.t {
+                Type::Array(right) => left.partial_cmp(right),
+                _ => None,
+            },
+            Type::Map(_) | Type::Struct(_) => None,
+            Type::Nil => other.is_nil().then_some(std::cmp::Ordering::Equal),
         }
     }
 }
@@ -179,15 +527,55 @@ impl PartialOrd for Value {
 impl Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.t {
-            Type::Int(l) => write!(f, "{l}"),
+            Type::Int(l) => match f.precision() {
+                Some(p) => write!(f, "{:.p$}", *l as f64),
+                None => write!(f, "{l}"),
+            },
             Type::Float(l) => {
-                if l.fract() == 0. {
+                if let Some(p) = f.precision() {
+                    write!(f, "{l:.p$}")
+                } else if l.fract() == 0. {
                     write!(f, "{l:.1}")
                 } else {
                     write!(f, "{l}")
                 }
             }
-            Type::Str(s) => write!(f, "{s}"),
+            Type::Str(s) => match f.precision() {
+                Some(p) => write!(f, "{}", s.chars().take(p).collect::<String>()),
+                None => write!(f, "{s}"),
+            },
+            Type::Bool(b) => write!(f, "{b}"),
+            Type::Array(a) => {
+                write!(f, "[")?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{v}")?;
+                }
+                write!(f, "]")
+            }
+            Type::Map(m) => {
+                write!(f, "#{{")?;
+                for (i, (k, v)) in m.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{k} {v}")?;
+                }
+                write!(f, "}}")
+            }
+            Type::Struct(s) => {
+                write!(f, "{}(", s.name)?;
+                for (i, (k, v)) in s.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{k}: {v}")?;
+                }
+                write!(f, ")")
+            }
+            Type::Nil => write!(f, "nil"),
         }
     }
 }
@@ -211,7 +599,7 @@ impl From<f64> for Value {
 impl From<bool> for Value {
     fn from(value: bool) -> Self {
         Self {
-            t: Type::Int(if value { 1 } else { 0 }),
+            t: Type::Bool(value),
         }
     }
 }
@@ -231,3 +619,36 @@ impl From<&str> for Value {
         }
     }
 }
+
+impl From<Vec<Value>> for Value {
+    fn from(value: Vec<Value>) -> Self {
+        Self {
+            t: Type::Array(value),
+        }
+    }
+}
+
+impl<T> From<Option<T>> for Value
+where
+    T: Into<Value>,
+{
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::nil(), Into::into)
+    }
+}
+
+impl From<Map> for Value {
+    fn from(value: Map) -> Self {
+        Self {
+            t: Type::Map(value),
+        }
+    }
+}
+
+impl From<Struct> for Value {
+    fn from(value: Struct) -> Self {
+        Self {
+            t: Type::Struct(value),
+        }
+    }
+}
@@ -0,0 +1,386 @@
+use std::cell::RefCell;
+use std::io::Cursor;
+use std::rc::Rc;
+
+use night::interpreter::Night;
+use night::lexer::Lexer;
+use night::utils::error::{NightError, Status};
+
+struct Captured {
+    status: Status,
+    out: String,
+    err: String,
+}
+
+// Runs `code` with both writers captured in memory
+fn run(code: &str) -> Captured {
+    capture(code, false)
+}
+
+// Only parses and checks `code` with strict types, like `night check`
+fn check(code: &str) -> Captured {
+    capture(code, true)
+}
+
+fn capture(code: &str, check_only: bool) -> Captured {
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let err = Rc::new(RefCell::new(Vec::new()));
+
+    let mut night = Night::new();
+    night.set_output(out.clone(), err.clone());
+    night.set_strict_types(check_only);
+    let status = Lexer::new(code)
+        .with_errors(night.out())
+        .tokenize()
+        .and_then(|tokens| night.push_new_code(code, tokens))
+        .and_then(|_| if check_only { Ok(()) } else { night.exec() });
+
+    let text = |buf: Rc<RefCell<Vec<u8>>>| String::from_utf8(buf.take()).unwrap();
+    Captured {
+        status,
+        out: text(out),
+        err: text(err),
+    }
+}
+
+#[test]
+fn print_and_write_go_to_out() {
+    let run = run("1 2 + print \"a\" write \"b\" print");
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "3\nab\n");
+    assert_eq!(run.err, "");
+}
+
+#[test]
+fn eprint_goes_to_err() {
+    let run = run("\"oops\" eprint");
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "");
+    assert_eq!(run.err, "oops\n");
+}
+
+#[test]
+fn runtime_errors_are_reported_without_exiting() {
+    let run = run("1 print \"a\" 1 + 2 print");
+    assert!(matches!(run.status, Err(NightError::UnsupportedType(_))));
+    assert!(run.out.starts_with("1\nTypeError: Cannot add 'a' and '1'."));
+    assert!(!run.out.contains("\n2\n"));
+}
+
+#[test]
+fn lex_errors_are_reported_without_exiting() {
+    let run = run("\"abc");
+    assert!(matches!(run.status, Err(NightError::Lex(_))));
+    assert!(run.out.starts_with("LexError: String not terminated."));
+}
+
+#[test]
+fn exit_returns_its_code() {
+    let run = run("\"bye\" print 3 exit \"unreachable\" print");
+    assert!(matches!(run.status, Err(NightError::Exit(3))));
+    assert_eq!(run.out, "bye\n");
+}
+
+#[test]
+fn exit_rejects_codes_out_of_range() {
+    let run = run("4294967296 exit");
+    assert!(matches!(run.status, Err(NightError::Runtime(_))));
+    assert!(run
+        .out
+        .starts_with("RuntimeError: Exit code 4294967296 is out of range."));
+}
+
+#[test]
+fn nights_share_stdin() {
+    // Neither holds stdin, so the second doesn't wait on the first
+    let first = Night::new();
+    let second = Night::new();
+    drop((first, second));
+}
+
+#[test]
+fn input_can_be_injected() {
+    let code = "read_line print { \"> \" : + print } lines read_line nil = print";
+    let out = Rc::new(RefCell::new(Vec::new()));
+    let mut night = Night::new();
+    night.set_output(out.clone(), out.clone());
+    night.set_input(Rc::new(RefCell::new(Cursor::new("one\ntwo\r\nthree"))));
+    let tokens = Lexer::new(code).tokenize().unwrap();
+    night.push_new_code(code, tokens).unwrap();
+    night.exec().unwrap();
+    assert_eq!(
+        String::from_utf8(out.take()).unwrap(),
+        "one\n> two\n> three\ntrue\n"
+    );
+}
+
+#[test]
+fn errors_in_constants_are_reported_once() {
+    let run = run("-> x| \"a\" 1 +\nx print");
+    assert!(matches!(run.status, Err(NightError::UnsupportedType(_))));
+    assert_eq!(run.out.matches("TypeError").count(), 1);
+}
+
+#[test]
+fn file_errors_come_back_as_io_errors() {
+    let run = run("\"/nonexistent/night/file.txt\" read_file print");
+    let Err(NightError::Io(msg)) = run.status else {
+        panic!("expected an IOError, got {:?}", run.status);
+    };
+    assert!(msg.starts_with("Could not read '/nonexistent/night/file.txt': "));
+    assert!(run.out.starts_with("IOError: Could not read"));
+}
+
+#[test]
+fn quotations_convert_to_and_from_arrays() {
+    let arrays = run("[ 1 \"a\" ] >quot . print call print print { 1 2 } quot> print");
+    assert!(arrays.status.is_ok());
+    assert_eq!(arrays.out, "{ 1 \"a\" }\na\n1\n[1 2]\n");
+
+    let words = run("{ 1 dup } quot>");
+    assert!(matches!(words.status, Err(NightError::UnsupportedType(_))));
+}
+
+#[test]
+fn checker_accepts_correct_programs() {
+    let checked = check(
+        "-> add ( a b -- c ) +\n\
+         -> addn ( a n -- a ) { + } times\n\
+         -> twice { . + }\n\
+         3 4 add twice 5 addn [ 1 2 ] len + print",
+    );
+    assert!(checked.status.is_ok(), "{}", checked.out);
+    assert_eq!(checked.out, "");
+}
+
+#[test]
+fn checker_reports_wrong_types_with_a_span() {
+    let checked = check("1 2 +\n\"a\" 1 call");
+    assert!(matches!(
+        checked.status,
+        Err(NightError::UnsupportedType(_))
+    ));
+    assert!(checked
+        .out
+        .starts_with("TypeError: Expected function, got Int at slot 1. [(1:6) => (1:10)]"));
+
+    // Without strict types, the same code only fails once it runs
+    let ran = run("\"a\" 1 call");
+    assert!(matches!(ran.status, Err(NightError::UnsupportedType(_))));
+}
+
+#[test]
+fn checker_reports_wrong_arities_with_a_span() {
+    let checked = check("-> sq ( a -- b c ) . *");
+    assert!(matches!(checked.status, Err(NightError::StackEffect(_))));
+    assert!(checked.out.starts_with(
+        "StackEffectError: 'sq' is declared as ( a -- b c ), but its body has the effect \
+         (1 in, 1 out). [(0:3) => (0:5)]"
+    ));
+
+    let ran = run("-> f { 1 [ pop ] }");
+    assert!(matches!(ran.status, Err(NightError::StackEffect(_))));
+    assert!(ran
+        .out
+        .starts_with("StackEffectError: array body consumes values from outside of the array."));
+}
+
+#[test]
+fn strings_process_escapes() {
+    let run = run(r#""a\tb\n\"q\" \\ \' \u{1F600}" print"#);
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "a\tb\n\"q\" \\ ' \u{1F600}\n");
+}
+
+#[test]
+fn invalid_escapes_are_reported_with_their_span() {
+    let unknown = run(r#""ab\qc" print"#);
+    assert!(matches!(unknown.status, Err(NightError::Lex(_))));
+    assert!(unknown
+        .out
+        .starts_with("LexError: Unknown escape sequence. [(0:3) => (0:5)]"));
+
+    let codepoint = run(r#""\u{110000}" print"#);
+    assert!(matches!(codepoint.status, Err(NightError::Lex(_))));
+    assert!(codepoint
+        .out
+        .starts_with("LexError: Invalid unicode codepoint."));
+}
+
+#[test]
+fn raw_strings_keep_backslashes_and_quotes() {
+    let run = run(r###"r"a\nb" print r#"say "hi" \t"# print"###);
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "a\\nb\nsay \"hi\" \\t\n");
+}
+
+#[test]
+fn number_literals() {
+    let run = run(
+        "1_000 print 0xFF print 0b1010 print 0o17 print -0x10 print 1e3 print \
+         1.5e-1 print .5 print 2i64 print 3f64 print",
+    );
+    assert!(run.status.is_ok());
+    assert_eq!(
+        run.out,
+        "1000\n255\n10\n15\n-16\n1000.0\n0.15\n0.5\n2\n3.0\n"
+    );
+}
+
+#[test]
+fn invalid_numbers_are_reported_with_their_span() {
+    let range = run("1 print 99999999999999999999 print");
+    assert!(matches!(range.status, Err(NightError::Syntax(_))));
+    assert!(range.out.starts_with(
+        "SyntaxError: Integer literal '99999999999999999999' is out of range for i64. \
+         [(0:8) => (0:28)]"
+    ));
+
+    let suffix = run("1.5i64");
+    assert!(matches!(suffix.status, Err(NightError::Syntax(_))));
+    assert!(suffix
+        .out
+        .starts_with("SyntaxError: Float literal '1.5i64' cannot have an 'i64' suffix."));
+
+    let digits = run("0x");
+    assert!(matches!(digits.status, Err(NightError::Syntax(_))));
+    assert!(digits
+        .out
+        .starts_with("SyntaxError: Missing digits in hexadecimal literal '0x'."));
+}
+
+#[test]
+fn declared_effects_that_match_run() {
+    let run = run("-> fact ( n -- n ) { . 1 > { . 1 - fact * } { } if }\n\
+         -> app ( x q -- y ) { call }\n\
+         -> six ( -- y ) 5 { inc } app\n\
+         -> apply ( ..a q -- ..b ) { call }\n\
+         5 fact print six print 1 2 { + } apply print");
+    assert!(run.status.is_ok(), "{}", run.out);
+    assert_eq!(run.out, "120\n6\n3\n");
+}
+
+#[test]
+fn declared_effects_that_mismatch_are_reported_before_running() {
+    let body = run("\"unreachable\" print\n-> f ( a b -- c ) + 1");
+    assert!(matches!(body.status, Err(NightError::StackEffect(_))));
+    assert!(body.out.starts_with(
+        "StackEffectError: 'f' is declared as ( a b -- c ), but its body has the effect \
+         (2 in, 2 out). [(1:3) => (1:4)]"
+    ));
+
+    // Code using a declared word is checked with its declared effect
+    let caller = run("-> h ( a -- a b ) { . inc }\n-> k ( a -- a ) { h }");
+    assert!(matches!(caller.status, Err(NightError::StackEffect(_))));
+    assert!(caller.out.starts_with(
+        "StackEffectError: 'k' is declared as ( a -- a ), but its body has the effect \
+         (1 in, 2 out)."
+    ));
+}
+
+#[test]
+fn while_until_and_do_while() {
+    let run = run("0 { . 3 < } { . print inc } while pop\n\
+         0 { . 3 = } { inc } until print\n\
+         10 { inc } { . 5 < } do_while print");
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "0\n1\n2\n3\n11\n");
+}
+
+#[test]
+fn break_and_continue_affect_the_innermost_loop() {
+    let loops = run("0 { . 10 < } { inc . 3 = { break } { } if } while print\n\
+         0 { . 5 < } { inc . 2 % 0 = { continue } { } if . print } while pop\n\
+         { 1 } { break } while\n\
+         0 { . 2 < } { inc 0 { 1 } { break } while pop } while print");
+    assert!(loops.status.is_ok());
+    assert_eq!(loops.out, "3\n1\n3\n5\n2\n");
+
+    let outside = run("break");
+    assert!(matches!(outside.status, Err(NightError::Runtime(_))));
+    assert!(outside
+        .out
+        .starts_with("RuntimeError: 'break' can only be used inside a loop."));
+}
+
+#[test]
+fn ranges() {
+    let ranges = run("1 5 range print 10 0 -3 range_by print 3 3 range print");
+    assert!(ranges.status.is_ok());
+    assert_eq!(ranges.out, "[1 2 3 4]\n[10 7 4 1]\n[]\n");
+
+    let step = run("1 2 0 range_by");
+    assert!(matches!(step.status, Err(NightError::Runtime(_))));
+    assert!(step
+        .out
+        .starts_with("RuntimeError: 'range_by' cannot take a step of 0."));
+}
+
+#[test]
+fn times_for_and_each_index() {
+    let run = run("3 { print } times\n\
+         0 [ 1 2 3 ] { + } for print\n\
+         \"ab\" { print } for #{ :k 1 } { print } for\n\
+         [ \"a\" \"b\" ] { print print } each_index\n\
+         [ 1 2 3 ] { . 2 = { break } { } if print } for");
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "0\n1\n2\n6\na\nb\n[k 1]\n0\na\n1\nb\n1\n");
+}
+
+#[test]
+fn large_loops_run_lazily() {
+    // Each iteration re-runs the body from its frame, nothing is queued up front
+    let run = run("0 1000000 { inc } loop print\n\
+         0 100000 { + } times print\n\
+         0 0 100000 range { + } for print\n\
+         0 { . 100000 < } { inc } while print");
+    assert!(run.status.is_ok());
+    assert_eq!(run.out, "1000000\n4999950000\n4999950000\n100000\n");
+}
+
+#[test]
+fn csv_header_mode() {
+    let parsed = run(r#""name,age\nann,30\nbob" #{ :header true } csv_parse_with json_str print"#);
+    assert!(parsed.status.is_ok());
+    assert_eq!(
+        parsed.out,
+        "[{\"name\":\"ann\",\"age\":\"30\"},{\"name\":\"bob\",\"age\":null}]\n"
+    );
+
+    // Columns are the union of every row's keys
+    let written = run(r#"[ #{ :a 1 } #{ :b 2 :a 3 } ] #{ } csv_str_with write"#);
+    assert!(written.status.is_ok());
+    assert_eq!(written.out, "a,b\n1,\n3,2\n");
+
+    let headless =
+        run(r#"[ #{ :a 1 } #{ :b 2 } ] #{ :header false :delim ";" } csv_str_with write"#);
+    assert!(headless.status.is_ok());
+    assert_eq!(headless.out, "1;\n;2\n");
+}
+
+#[test]
+fn escaped_braces_do_not_start_templates() {
+    let plain = run(r#""a \{$b" print "\{ \}" print"#);
+    assert!(plain.status.is_ok());
+    assert_eq!(plain.out, "a {$b\n{ }\n");
+
+    let template = run(r#"5 $x! "\{$x\} = {$x:.1}" print"#);
+    assert!(template.status.is_ok());
+    assert_eq!(template.out, "{$x} = 5.0\n");
+}
+
+#[test]
+fn struct_words_are_only_made_by_declarations() {
+    let declared = run("-> struct p (x y)\n\
+         1 2 p . p.x print 3 p.set_y . p.y print p? print 5 p? print");
+    assert!(declared.status.is_ok());
+    assert_eq!(declared.out, "1\n3\ntrue\nfalse\n");
+
+    for word in ["struct_new", "get_field", "set_field", "is_a"] {
+        let internal = run(&format!("-> struct p (x y)\n1 2 p \"p\" \"z\" {word}"));
+        assert!(
+            matches!(internal.status, Err(NightError::UndefinedSymbol(_))),
+            "{word}"
+        );
+    }
+}